@@ -1,8 +1,39 @@
-use async_graphql::{Context, Error, Result};
+use std::env;
+
+use async_graphql::{
+    parser::{parse_query, types::OperationType},
+    Context, Error, Result,
+};
 use axum::http::HeaderMap;
 use bson::Uuid;
+use once_cell::sync::Lazy;
 use serde::Deserialize;
 
+use crate::{
+    error::ShoppingCartError, graphql::model::shoppingcart_member::ShoppingCartMemberPermission,
+};
+use jwt::JwtVerifier;
+use permission::{Access, Permission, RolePermissions};
+
+pub mod guard;
+pub mod jwt;
+pub mod permission;
+
+/// Verifier of bearer JWTs, JWT verification is disabled if not configured.
+static JWT_VERIFIER: Lazy<Option<JwtVerifier>> = Lazy::new(JwtVerifier::from_env);
+
+/// Permissions granted by roles.
+static ROLE_PERMISSIONS: Lazy<RolePermissions> = Lazy::new(RolePermissions::from_env);
+
+/// Secret the trusted internal gateway sends in the `Trusted-Gateway-Secret` header, read from `$TRUSTED_GATEWAY_SECRET`.
+///
+/// No request is trusted if the variable is not set.
+static TRUSTED_GATEWAY_SECRET: Lazy<Option<String>> = Lazy::new(|| {
+    env::var("TRUSTED_GATEWAY_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+});
+
 /// `Authorized-User` HTTP header.
 #[derive(Deserialize, Debug)]
 pub struct AuthorizedUserHeader {
@@ -25,71 +56,319 @@ impl TryFrom<&HeaderMap> for AuthorizedUserHeader {
                 return Ok(authorized_user_header);
             }
         }
-        Err(Error::new(
-            "Authorization failed. Authorized-User header is not set or could not be parsed.",
-        ))
+        Err(ShoppingCartError::Unauthenticated {
+            message:
+                "Authorization failed. Authorized-User header is not set or could not be parsed."
+                    .to_string(),
+        }
+        .into())
+    }
+}
+
+/// Initializes the role permissions and JWT verification, so invalid configurations are detected on startup.
+///
+/// Returns whether JWT verification is enabled.
+pub fn init_authorization() -> bool {
+    Lazy::force(&ROLE_PERMISSIONS);
+    JWT_VERIFIER.is_some()
+}
+
+/// Authenticates the user of a request.
+///
+/// Without JWT verification, the `Authorized-User` header is trusted.
+/// With JWT verification, the user is taken from the verified bearer JWT of the `Authorization` header.
+/// An `Authorized-User` header sent along must then describe the same user with the same roles, and is rejected without a bearer JWT.
+///
+/// Returns `None` if the request is unauthenticated.
+///
+/// * `header_map` - Header map containing headers of request.
+pub fn authenticate(header_map: &HeaderMap) -> Result<Option<AuthorizedUserHeader>> {
+    let maybe_authorized_user_header = AuthorizedUserHeader::try_from(header_map).ok();
+    let Some(jwt_verifier) = JWT_VERIFIER.as_ref() else {
+        return Ok(maybe_authorized_user_header);
+    };
+    let maybe_token = header_map
+        .get("Authorization")
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "));
+    match (maybe_token, maybe_authorized_user_header) {
+        (Some(token), maybe_authorized_user_header) => {
+            let token_user = jwt_verifier.verify(token)?;
+            match maybe_authorized_user_header {
+                Some(authorized_user_header) if !authorized_user_header.describes_same_user(&token_user) => Err(ShoppingCartError::Unauthenticated {
+                    message: "Authentication failed. Authorized-User header does not match bearer token.".to_string(),
+                }.into()),
+                _ => Ok(Some(token_user)),
+            }
+        }
+        (None, Some(_)) => Err(ShoppingCartError::Unauthenticated {
+            message: "Authentication failed. Authorized-User header is not accepted without bearer token.".to_string(),
+        }
+        .into()),
+        (None, None) => Ok(None),
+    }
+}
+
+impl AuthorizedUserHeader {
+    /// Defines if another header describes the same user with the same roles.
+    ///
+    /// * `other` - Header to compare with.
+    fn describes_same_user(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.roles.iter().all(|role| other.roles.contains(role))
+            && other.roles.iter().all(|role| self.roles.contains(role))
     }
 }
 
+/// Marks a request as sent by the trusted internal gateway.
+///
+/// Reads of the trusted gateway pass owner guards regardless of the `Authorized-User` header, modifications still require an authorized user.
+#[derive(Debug, Clone, Copy)]
+pub struct TrustedGateway;
+
+impl TrustedGateway {
+    /// Checks if a request was sent by the trusted internal gateway.
+    ///
+    /// Returns `None` if `$TRUSTED_GATEWAY_SECRET` is not set or the `Trusted-Gateway-Secret` header does not match it.
+    ///
+    /// * `header_map` - Header map containing headers of request.
+    pub fn from_headers(header_map: &HeaderMap) -> Option<Self> {
+        let trusted_gateway_secret = TRUSTED_GATEWAY_SECRET.as_ref()?;
+        let secret = header_map.get("Trusted-Gateway-Secret")?.as_bytes();
+        constant_time_eq(secret, trusted_gateway_secret.as_bytes()).then_some(Self)
+    }
+}
+
+/// Marks a request of a user permitted to read data of all users impersonating another user with the `Impersonate-User` header.
+///
+/// Impersonated requests resolve data of the authorized user for the impersonated user, but must not contain mutations.
+#[derive(Debug, Clone, Copy)]
+pub struct Impersonation {
+    /// UUID of the impersonated user.
+    user_id: Uuid,
+}
+
+impl Impersonation {
+    /// Extracts the impersonated user from the `Impersonate-User` header.
+    ///
+    /// Returns a GraphQL error if the header could not be parsed or the authorized user is not permitted to read data of all users.
+    ///
+    /// * `header_map` - Header map containing headers of request.
+    /// * `authorized_user_header` - Option of the authenticated `Authorized-User` header of the request.
+    pub fn from_headers(
+        header_map: &HeaderMap,
+        authorized_user_header: Option<&AuthorizedUserHeader>,
+    ) -> Result<Option<Self>> {
+        let Some(impersonate_user_header_value) = header_map.get("Impersonate-User") else {
+            return Ok(None);
+        };
+        let user_id = impersonate_user_header_value
+            .to_str()
+            .ok()
+            .and_then(|impersonate_user_header_str| {
+                Uuid::parse_str(impersonate_user_header_str).ok()
+            })
+            .ok_or_else(|| ShoppingCartError::Validation {
+                message: "Impersonation failed. Impersonate-User header could not be parsed."
+                    .to_string(),
+                id: None,
+            })?;
+        match authorized_user_header {
+            Some(authorized_user_header)
+                if authorized_user_header.has_permission(Permission::ReadAll) =>
+            {
+                Ok(Some(Self { user_id }))
+            }
+            _ => Err(ShoppingCartError::Forbidden {
+                message: "Impersonation failed. Impersonate-User header is only accepted for users permitted to read data of all users.".to_string(),
+                user_id: authorized_user_header.map(|authorized_user_header| authorized_user_header.id),
+            }
+            .into()),
+        }
+    }
+
+    /// UUID of the impersonated user.
+    pub fn user_id(&self) -> Uuid {
+        self.user_id
+    }
+
+    /// Checks that a GraphQL query does not contain mutations, as impersonated requests are read-only.
+    ///
+    /// * `query` - GraphQL query of the impersonated request.
+    pub fn check_read_only(&self, query: &str) -> Result<()> {
+        match contains_mutation(query) {
+            true => {
+                let message = format!(
+                    "Mutations are not permitted while impersonating user of UUID: `{}`.",
+                    self.user_id
+                );
+                Err(ShoppingCartError::Forbidden {
+                    message,
+                    user_id: None,
+                }
+                .into())
+            }
+            false => Ok(()),
+        }
+    }
+}
+
+/// Defines if a GraphQL query contains a mutation operation.
+///
+/// Queries which cannot be parsed are treated as not containing mutations, as the schema rejects them.
+///
+/// * `query` - GraphQL query of a request.
+pub fn contains_mutation(query: &str) -> bool {
+    parse_query(query)
+        .map(|document| {
+            document
+                .operations
+                .iter()
+                .any(|(_, operation)| operation.node.ty == OperationType::Mutation)
+        })
+        .unwrap_or(false)
+}
+
+/// Compares two byte slices in time independent of the position of the first difference.
+///
+/// * `first` - First byte slice to compare.
+/// * `second` - Second byte slice to compare.
+fn constant_time_eq(first: &[u8], second: &[u8]) -> bool {
+    first.len() == second.len()
+        && first
+            .iter()
+            .zip(second)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
 /// Role of user.
-#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
-#[serde(rename_all = "snake_case")]
-enum Role {
+///
+/// Roles unknown to the service are preserved, so they can be granted permissions.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(from = "String")]
+pub enum Role {
     Buyer,
     Admin,
     Employee,
+    Other(String),
+}
+
+impl From<String> for Role {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "buyer" => Self::Buyer,
+            "admin" => Self::Admin,
+            "employee" => Self::Employee,
+            _ => Self::Other(value),
+        }
+    }
 }
 
 impl Role {
-    /// Defines if user has a permissive role.
-    fn is_permissive(self) -> bool {
+    /// Name of the role as sent by the identity provider.
+    pub fn name(&self) -> &str {
         match self {
-            Self::Buyer => false,
-            Self::Admin => true,
-            Self::Employee => true,
+            Self::Buyer => "buyer",
+            Self::Admin => "admin",
+            Self::Employee => "employee",
+            Self::Other(name) => name,
         }
     }
 }
 
-/// Authorize user of UUID for a context.
+impl AuthorizedUserHeader {
+    /// UUID of the user.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Names of the roles of the user.
+    pub fn role_names(&self) -> Vec<String> {
+        self.roles
+            .iter()
+            .map(|role| role.name().to_string())
+            .collect()
+    }
+
+    /// Defines if any role of the user grants a permission.
+    ///
+    /// * `permission` - Permission to check.
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        ROLE_PERMISSIONS.grants(&self.roles, permission)
+    }
+}
+
+/// Retrieves the UUID of the user a context is authorized for.
 ///
-/// * `context` - GraphQL context containing the `Authorized-User` header.
-/// * `id` - Option of UUID of the user to authorize.
-pub fn authorize_user(ctx: &Context, id: Option<Uuid>) -> Result<()> {
-    match ctx.data::<AuthorizedUserHeader>() {
-        Ok(authorized_user_header) => check_permissions(&authorized_user_header, id),
-        Err(_) => Err(Error::new(
-            "Authentication failed. Authorized-User header is not set or could not be parsed.",
-        )),
+/// Resolves to the impersonated user if the request impersonates another user.
+///
+/// * `context` - GraphQL context containing the `Authorized-User` header and the impersonation.
+pub fn authorized_user_id(ctx: &Context) -> Result<Uuid> {
+    let authorized_user_header = authorized_user_header(ctx)?;
+    match ctx.data_opt::<Impersonation>() {
+        Some(impersonation) => Ok(impersonation.user_id),
+        None => Ok(authorized_user_header.id),
     }
 }
 
+/// Retrieves the `Authorized-User` header of a context.
+///
+/// * `context` - GraphQL context containing the `Authorized-User` header.
+pub fn authorized_user_header<'a>(ctx: &'a Context) -> Result<&'a AuthorizedUserHeader> {
+    ctx.data::<AuthorizedUserHeader>().map_err(|_| {
+        ShoppingCartError::Unauthenticated {
+            message:
+                "Authentication failed. Authorized-User header is not set or could not be parsed."
+                    .to_string(),
+        }
+        .into()
+    })
+}
+
 /// Check if user of UUID has a valid permission according to the `Authorized-User` header.
 ///
-/// Permission is valid if the user has `Role::Buyer` and the same UUID as provided in the function parameter.
-/// Permission is valid if the user has a permissive role: `user.is_permissive() == true`, regardless of the users UUID.
+/// Permission is valid if the user has the permission for the access to data of all users, regardless of the users UUID.
+/// Permission is valid if the user has the permission for the access to own data and the same UUID as provided in the function parameter.
+/// Permission is valid if the user has the permission for the access to own data and was invited to the shopping cart with a member permission granting the access.
 ///
 /// * `authorized_user_header` - `Authorized-User` header containing the users UUID and role.
 /// * `id` - Option of UUID of the user to authorize.
+/// * `member_permission` - Option of the member permission of the authorized user on the shopping cart of the user to authorize.
+/// * `access` - Kind of access to the data of the user.
 pub fn check_permissions(
     authorized_user_header: &AuthorizedUserHeader,
     id: Option<Uuid>,
+    member_permission: Option<ShoppingCartMemberPermission>,
+    access: Access,
 ) -> Result<()> {
     let id_contained_in_header = id
-        .and_then(|id| Some(authorized_user_header.id == id))
+        .map(|id| authorized_user_header.id == id)
+        .unwrap_or(false);
+    let access_granted_to_member = member_permission
+        .map(|member_permission| member_permission.grants(access))
         .unwrap_or(false);
-    if authorized_user_header
-        .roles
-        .iter()
-        .any(|role| role.is_permissive())
-        || id_contained_in_header
+    if authorized_user_header.has_permission(access.all_permission())
+        || ((id_contained_in_header || access_granted_to_member)
+            && authorized_user_header.has_permission(access.own_permission()))
     {
-        return Ok(());
+        Ok(())
     } else {
-        let message = format!(
-            "Authentication failed for user of UUID: `{}`. Operation not permitted.",
-            authorized_user_header.id
-        );
-        return Err(Error::new(message));
+        Err(operation_not_permitted(authorized_user_header))
+    }
+}
+
+/// Builds the error returned if the user of the `Authorized-User` header is not permitted to perform an operation.
+///
+/// * `authorized_user_header` - `Authorized-User` header containing the users UUID and role.
+fn operation_not_permitted(authorized_user_header: &AuthorizedUserHeader) -> Error {
+    let message = format!(
+        "Authentication failed for user of UUID: `{}`. Operation not permitted.",
+        authorized_user_header.id
+    );
+    ShoppingCartError::Forbidden {
+        message,
+        user_id: Some(authorized_user_header.id),
     }
+    .into()
 }
@@ -0,0 +1,196 @@
+use async_graphql::{Context, Error, Guard, Result, Value};
+use bson::Uuid;
+use mongodb::{Collection, Database};
+
+use crate::graphql::{
+    model::user::User,
+    query::{query_shoppingcart, query_shoppingcart_item_user},
+};
+
+use super::{
+    authorized_user_header, authorized_user_id, check_permissions, operation_not_permitted,
+    permission::{Access, Permission},
+    Role, TrustedGateway,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Reference to the user owning guarded data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Owner {
+    /// User of UUID.
+    User(Uuid),
+    /// User owning the shopping cart item of UUID, only queried if the owner is needed.
+    ShoppingCartItem(Uuid),
+    /// User the request is authorized for.
+    AuthorizedUser,
+}
+
+/// Guard permitting access to data of a user for the user itself, invited members of its shopping cart, users permitted to access data of all users and reads of the trusted internal gateway.
+pub struct OwnerOrPermissive {
+    /// User owning the guarded data.
+    owner: Owner,
+    /// Kind of access to the guarded data.
+    access: Access,
+    /// Whether members of the shopping cart of the owning user are permitted according to their member permission.
+    members_permitted: bool,
+}
+
+impl OwnerOrPermissive {
+    /// Creates a guard for reading data of a user.
+    ///
+    /// * `user_id` - UUID of the user owning the guarded data.
+    pub fn user(user_id: Uuid) -> Self {
+        Self {
+            owner: Owner::User(user_id),
+            access: Access::Read,
+            members_permitted: true,
+        }
+    }
+
+    /// Creates a guard for reading a shopping cart item, resolving its owning user only if needed.
+    ///
+    /// * `shoppingcart_item_id` - UUID of the guarded shopping cart item.
+    pub fn shoppingcart_item(shoppingcart_item_id: Uuid) -> Self {
+        Self {
+            owner: Owner::ShoppingCartItem(shoppingcart_item_id),
+            access: Access::Read,
+            members_permitted: true,
+        }
+    }
+
+    /// Creates a guard for reading data of the user the request is authorized for.
+    pub fn authorized_user() -> Self {
+        Self {
+            owner: Owner::AuthorizedUser,
+            access: Access::Read,
+            members_permitted: true,
+        }
+    }
+
+    /// Guards modifications instead of reads of the data.
+    pub fn write(self) -> Self {
+        Self {
+            access: Access::Write,
+            ..self
+        }
+    }
+
+    /// Denies access to members of the shopping cart of the owning user, regardless of their member permission.
+    pub fn excluding_members(self) -> Self {
+        Self {
+            members_permitted: false,
+            ..self
+        }
+    }
+
+    /// Resolves the UUID of the user owning the guarded data.
+    ///
+    /// * `context` - GraphQL context containing the MongoDB database and the `Authorized-User` header.
+    async fn owner_id(&self, ctx: &Context<'_>) -> Result<Uuid> {
+        match self.owner {
+            Owner::User(user_id) => Ok(user_id),
+            Owner::ShoppingCartItem(shoppingcart_item_id) => {
+                let db_client = ctx.data::<Database>()?;
+                let collection: Collection<User> = db_client.collection::<User>("users");
+                let user = query_shoppingcart_item_user(&collection, shoppingcart_item_id).await?;
+                Ok(user._id)
+            }
+            Owner::AuthorizedUser => authorized_user_id(ctx),
+        }
+    }
+}
+
+impl Guard for OwnerOrPermissive {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        if self.access == Access::Read && ctx.data_opt::<TrustedGateway>().is_some() {
+            return Ok(());
+        }
+        let authorized_user_header = authorized_user_header(ctx)?;
+        if authorized_user_header.has_permission(self.access.all_permission()) {
+            return Ok(());
+        }
+        let owner_id = match self.owner_id(ctx).await {
+            Ok(owner_id) => owner_id,
+            Err(error) if is_not_found(&error) => {
+                return Err(operation_not_permitted(authorized_user_header))
+            }
+            Err(error) => return Err(error),
+        };
+        let member_permission = match self.members_permitted
+            && owner_id != authorized_user_header.id
+        {
+            true => {
+                let db_client = ctx.data::<Database>()?;
+                let collection: Collection<User> = db_client.collection::<User>("users");
+                match query_shoppingcart(&collection, owner_id).await {
+                    Ok(shoppingcart) => shoppingcart.member_permission(authorized_user_header.id),
+                    Err(error) if is_not_found(&error) => None,
+                    Err(error) => return Err(error),
+                }
+            }
+            false => None,
+        };
+        check_permissions(
+            authorized_user_header,
+            Some(owner_id),
+            member_permission,
+            self.access,
+        )
+    }
+}
+
+/// Defines if an error signals that the owner of guarded data does not exist.
+///
+/// Users not permitted to access data of all users are denied instead, so they cannot probe which shopping carts and shopping cart items exist.
+///
+/// * `error` - Error of resolving the owner or its shopping cart.
+fn is_not_found(error: &Error) -> bool {
+    error
+        .extensions
+        .as_ref()
+        .and_then(|extensions| extensions.get("code"))
+        == Some(&Value::from("NOT_FOUND"))
+}
+
+/// Guard permitting access only for users whose roles grant a permission.
+pub struct RequirePermission(pub Permission);
+
+impl Guard for RequirePermission {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let authorized_user_header = authorized_user_header(ctx)?;
+        match authorized_user_header.has_permission(self.0) {
+            true => Ok(()),
+            false => Err(operation_not_permitted(authorized_user_header)),
+        }
+    }
+}
+
+/// Guard permitting access only for users with a specific role.
+pub struct RequireRole(pub Role);
+
+impl Guard for RequireRole {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        RequireAnyRole(std::slice::from_ref(&self.0))
+            .check(ctx)
+            .await
+    }
+}
+
+/// Guard permitting access only for users with any of the roles.
+pub struct RequireAnyRole<'a>(pub &'a [Role]);
+
+impl Guard for RequireAnyRole<'_> {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let authorized_user_header = authorized_user_header(ctx)?;
+        match authorized_user_header
+            .roles
+            .iter()
+            .any(|role| self.0.contains(role))
+        {
+            true => Ok(()),
+            false => Err(operation_not_permitted(authorized_user_header)),
+        }
+    }
+}
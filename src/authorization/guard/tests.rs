@@ -0,0 +1,742 @@
+//! Tests of the guards of every query, mutation, subscription and entity resolver.
+//!
+//! Requests run against the schema backed by a fake MongoDB, which answers the handshake and `find` commands on `users`, returns no documents for `aggregate` commands and fails every other command.
+//! Guards only read users, so each operation is either rejected by its guard or fails later in its resolver, which counts as permitted.
+
+use std::{sync::Arc, time::Duration};
+
+use async_graphql::{Request, Response, Schema, Value};
+use bson::{doc, Bson, DateTime, Document, Uuid};
+use futures::StreamExt;
+use mongodb::{Client, Database};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+use crate::{
+    authorization::{AuthorizedUserHeader, Role, TrustedGateway},
+    graphql::{
+        limits::ShoppingCartLimits,
+        model::{
+            foreign_types::ProductVariant,
+            shoppingcart::ShoppingCart,
+            shoppingcart_item::ShoppingCartItem,
+            shoppingcart_member::{ShoppingCartMember, ShoppingCartMemberPermission},
+            user::User,
+        },
+        mutation::Mutation,
+        query::Query,
+        subscription::{ShoppingCartUpdates, Subscription},
+    },
+};
+
+/// Opcode of `OP_MSG`, the only wire protocol message used by the MongoDB driver.
+const OP_MSG: i32 = 2013;
+
+/// Time after which a subscription without response is considered permitted, as permitted subscriptions wait for changes.
+const SUBSCRIPTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Outcome of an operation for an actor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    /// Guard passed, the resolver may still fail.
+    Permitted,
+    /// Guard failed with code `FORBIDDEN`.
+    Forbidden,
+    /// Guard failed with code `UNAUTHENTICATED`.
+    Unauthenticated,
+}
+
+use Outcome::{Forbidden, Permitted, Unauthenticated};
+
+/// Sender of a request.
+#[derive(Debug, Clone, Copy)]
+enum Actor {
+    /// Buyer owning the shopping cart.
+    Owner,
+    /// Buyer neither owning nor invited to the shopping cart.
+    ForeignBuyer,
+    /// Buyer invited to the shopping cart with `VIEW` permission.
+    ViewingMember,
+    /// Buyer invited to the shopping cart with `EDIT` permission.
+    EditingMember,
+    /// Employee, permitted to read and modify data of all users.
+    Employee,
+    /// Admin, permitted to read and modify data of all users.
+    Admin,
+    /// Request without `Authorized-User` header.
+    Anonymous,
+    /// Trusted internal gateway without `Authorized-User` header.
+    Gateway,
+}
+
+/// Actors in the order of the expected outcomes.
+const ACTORS: [Actor; 8] = [
+    Actor::Owner,
+    Actor::ForeignBuyer,
+    Actor::ViewingMember,
+    Actor::EditingMember,
+    Actor::Employee,
+    Actor::Admin,
+    Actor::Anonymous,
+    Actor::Gateway,
+];
+
+/// Expected outcomes of `OwnerOrPermissive` guarding reads.
+const OWNER_READ: [Outcome; 8] = [
+    Permitted,
+    Forbidden,
+    Permitted,
+    Permitted,
+    Permitted,
+    Permitted,
+    Unauthenticated,
+    Permitted,
+];
+
+/// Expected outcomes of `OwnerOrPermissive` guarding modifications.
+const OWNER_WRITE: [Outcome; 8] = [
+    Permitted,
+    Forbidden,
+    Forbidden,
+    Permitted,
+    Permitted,
+    Permitted,
+    Unauthenticated,
+    Unauthenticated,
+];
+
+/// Expected outcomes of `OwnerOrPermissive` guarding modifications excluding members.
+const OWNER_WRITE_EXCLUDING_MEMBERS: [Outcome; 8] = [
+    Permitted,
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Permitted,
+    Permitted,
+    Unauthenticated,
+    Unauthenticated,
+];
+
+/// Expected outcomes of `OwnerOrPermissive` guarding reads of data whose owner does not exist, which must not be revealed to buyers.
+const UNKNOWN_OWNER_READ: [Outcome; 8] = [
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Permitted,
+    Permitted,
+    Unauthenticated,
+    Permitted,
+];
+
+/// Expected outcomes of `OwnerOrPermissive` guarding data of the authorized user, the gateway has no authorized user.
+const AUTHORIZED_USER: [Outcome; 8] = [
+    Permitted,
+    Permitted,
+    Permitted,
+    Permitted,
+    Permitted,
+    Permitted,
+    Unauthenticated,
+    Unauthenticated,
+];
+
+/// Expected outcomes of `RequirePermission(Permission::ReadAll)`.
+const READ_ALL: [Outcome; 8] = [
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Permitted,
+    Permitted,
+    Unauthenticated,
+    Unauthenticated,
+];
+
+/// Expected outcomes of `RequireRole(Role::Admin)`.
+const ADMIN: [Outcome; 8] = [
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Forbidden,
+    Permitted,
+    Unauthenticated,
+    Unauthenticated,
+];
+
+/// Users and shopping cart stored in the fake MongoDB.
+struct Fixture {
+    owner_id: Uuid,
+    foreign_buyer_id: Uuid,
+    viewing_member_id: Uuid,
+    editing_member_id: Uuid,
+    shoppingcart_item_id: Uuid,
+    product_variant_id: Uuid,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        Self {
+            owner_id: Uuid::new(),
+            foreign_buyer_id: Uuid::new(),
+            viewing_member_id: Uuid::new(),
+            editing_member_id: Uuid::new(),
+            shoppingcart_item_id: Uuid::new(),
+            product_variant_id: Uuid::new(),
+        }
+    }
+
+    /// Users stored in the fake MongoDB, the owner invited both members to its shopping cart.
+    fn users(&self) -> Vec<Document> {
+        let mut shoppingcart = ShoppingCart::new();
+        shoppingcart
+            .internal_shoppingcart_items
+            .insert(ShoppingCartItem {
+                _id: self.shoppingcart_item_id,
+                count: 1,
+                added_at: DateTime::now(),
+                product_variant: ProductVariant::from(self.product_variant_id),
+                added_retail_price: None,
+            });
+        shoppingcart.members = vec![
+            ShoppingCartMember {
+                user_id: self.viewing_member_id,
+                permission: ShoppingCartMemberPermission::View,
+            },
+            ShoppingCartMember {
+                user_id: self.editing_member_id,
+                permission: ShoppingCartMemberPermission::Edit,
+            },
+        ];
+        let owner = User {
+            _id: self.owner_id,
+            shoppingcart,
+        };
+        let other_users = [
+            self.foreign_buyer_id,
+            self.viewing_member_id,
+            self.editing_member_id,
+        ]
+        .map(|id| User {
+            _id: id,
+            shoppingcart: ShoppingCart::new(),
+        });
+        std::iter::once(owner)
+            .chain(other_users)
+            .map(|user| bson::to_document(&user).unwrap())
+            .collect()
+    }
+
+    /// Replaces the `$owner`, `$foreignBuyer`, `$viewingMember`, `$shoppingcartItem` and `$productVariant` placeholders of a GraphQL query.
+    ///
+    /// * `query` - GraphQL query containing placeholders.
+    fn substitute(&self, query: &str) -> String {
+        query
+            .replace("$owner", &self.owner_id.to_string())
+            .replace("$foreignBuyer", &self.foreign_buyer_id.to_string())
+            .replace("$viewingMember", &self.viewing_member_id.to_string())
+            .replace("$shoppingcartItem", &self.shoppingcart_item_id.to_string())
+            .replace("$productVariant", &self.product_variant_id.to_string())
+    }
+
+    /// Builds a request of an actor.
+    ///
+    /// * `actor` - Sender of the request.
+    /// * `query` - GraphQL query of the request.
+    fn request(&self, actor: Actor, query: &str) -> Request {
+        let request = Request::new(query);
+        let (id, role) = match actor {
+            Actor::Owner => (self.owner_id, Role::Buyer),
+            Actor::ForeignBuyer => (self.foreign_buyer_id, Role::Buyer),
+            Actor::ViewingMember => (self.viewing_member_id, Role::Buyer),
+            Actor::EditingMember => (self.editing_member_id, Role::Buyer),
+            Actor::Employee => (Uuid::new(), Role::Employee),
+            Actor::Admin => (Uuid::new(), Role::Admin),
+            Actor::Anonymous => return request,
+            Actor::Gateway => return request.data(TrustedGateway),
+        };
+        request.data(AuthorizedUserHeader {
+            id,
+            roles: vec![role],
+        })
+    }
+}
+
+/// Executes a GraphQL query for every actor and asserts the outcomes.
+///
+/// * `query` - GraphQL query containing the placeholders of `Fixture::substitute`.
+/// * `expected_outcomes` - Outcomes in the order of `ACTORS`.
+async fn assert_guarded(query: &str, expected_outcomes: [Outcome; 8]) {
+    let fixture = Fixture::new();
+    let db_client = start_fake_mongodb(fixture.users()).await;
+    let schema = Schema::build(Query, Mutation, Subscription)
+        .data(db_client)
+        .data(ShoppingCartLimits::default())
+        .data(ShoppingCartUpdates::new())
+        .enable_federation()
+        .finish();
+    let query = fixture.substitute(query);
+    for (actor, expected_outcome) in ACTORS.into_iter().zip(expected_outcomes) {
+        let request = fixture.request(actor, &query);
+        let response = match query.starts_with("subscription") {
+            true => timeout(SUBSCRIPTION_TIMEOUT, schema.execute_stream(request).next())
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+            false => schema.execute(request).await,
+        };
+        assert_eq!(
+            outcome(&query, &response),
+            expected_outcome,
+            "{:?} executing {}: {:?}",
+            actor,
+            query,
+            response.errors
+        );
+    }
+}
+
+/// Classifies the response of an operation by the `code` extensions of its errors.
+///
+/// * `query` - Executed GraphQL query, used in the panic message.
+/// * `response` - Response of the operation.
+fn outcome(query: &str, response: &Response) -> Outcome {
+    let codes: Vec<&Value> = response
+        .errors
+        .iter()
+        .inspect(|error| {
+            assert!(
+                !error.path.is_empty(),
+                "{} is invalid: {}",
+                query,
+                error.message
+            )
+        })
+        .filter_map(|error| error.extensions.as_ref()?.get("code"))
+        .collect();
+    if codes.contains(&&Value::from("FORBIDDEN")) {
+        Forbidden
+    } else if codes.contains(&&Value::from("UNAUTHENTICATED")) {
+        Unauthenticated
+    } else {
+        Permitted
+    }
+}
+
+/// Starts a fake MongoDB on a free local port and connects to it.
+///
+/// * `users` - Documents of the `users` collection.
+async fn start_fake_mongodb(users: Vec<Document>) -> Database {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let users = Arc::new(users);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_connection(stream, users.clone()));
+        }
+    });
+    let uri = format!(
+        "mongodb://127.0.0.1:{}/?directConnection=true&serverSelectionTimeoutMS=2000",
+        port
+    );
+    Client::with_uri_str(uri)
+        .await
+        .unwrap()
+        .database("shoppingcart-database")
+}
+
+/// Answers `OP_MSG` commands of a connection until it is closed.
+///
+/// * `stream` - Connection of the driver.
+/// * `users` - Documents of the `users` collection.
+async fn serve_connection(mut stream: TcpStream, users: Arc<Vec<Document>>) -> std::io::Result<()> {
+    loop {
+        let mut header = [0u8; 16];
+        stream.read_exact(&mut header).await?;
+        let length = i32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let request_id = i32::from_le_bytes(header[4..8].try_into().unwrap());
+        let mut body = vec![0u8; length - header.len()];
+        stream.read_exact(&mut body).await?;
+        // Skips the flag bits, then reads the command from the body section of kind `0` and ignores document sequences.
+        let mut offset = 4;
+        let mut command = Document::new();
+        while offset < body.len() {
+            let kind = body[offset];
+            let size =
+                i32::from_le_bytes(body[offset + 1..offset + 5].try_into().unwrap()) as usize;
+            if kind == 0 {
+                command = Document::from_reader(&body[offset + 1..offset + 1 + size]).unwrap();
+            }
+            offset += 1 + size;
+        }
+        let mut reply = Vec::new();
+        respond(&command, &users).to_writer(&mut reply).unwrap();
+        let mut message = Vec::with_capacity(21 + reply.len());
+        message.extend_from_slice(&(21 + reply.len() as i32).to_le_bytes());
+        message.extend_from_slice(&0i32.to_le_bytes());
+        message.extend_from_slice(&request_id.to_le_bytes());
+        message.extend_from_slice(&OP_MSG.to_le_bytes());
+        message.extend_from_slice(&0u32.to_le_bytes());
+        message.push(0);
+        message.extend_from_slice(&reply);
+        stream.write_all(&message).await?;
+    }
+}
+
+/// Builds the reply to a command.
+///
+/// * `command` - Command sent by the driver.
+/// * `users` - Documents of the `users` collection.
+fn respond(command: &Document, users: &[Document]) -> Document {
+    let command_name = command
+        .keys()
+        .next()
+        .map(String::as_str)
+        .unwrap_or_default();
+    match command_name {
+        "hello" | "isMaster" | "ismaster" => doc! {
+            "helloOk": true,
+            "ismaster": true,
+            "isWritablePrimary": true,
+            "maxBsonObjectSize": 16 * 1024 * 1024,
+            "maxMessageSizeBytes": 48_000_000,
+            "maxWriteBatchSize": 100_000,
+            "localTime": DateTime::now(),
+            "logicalSessionTimeoutMinutes": 30,
+            "connectionId": 1,
+            "minWireVersion": 0,
+            "maxWireVersion": 17,
+            "ok": 1.0,
+        },
+        "find" => {
+            let collection = command.get_str("find").unwrap_or_default();
+            let filter = command.get_document("filter").cloned().unwrap_or_default();
+            let limit = match command.get("limit") {
+                Some(Bson::Int32(limit)) if *limit != 0 => limit.unsigned_abs() as usize,
+                Some(Bson::Int64(limit)) if *limit != 0 => limit.unsigned_abs() as usize,
+                _ => usize::MAX,
+            };
+            let first_batch: Vec<Document> = match collection {
+                "users" => users
+                    .iter()
+                    .filter(|user| matches_filter(user, &filter))
+                    .take(limit)
+                    .cloned()
+                    .collect(),
+                _ => Vec::new(),
+            };
+            doc! {
+                "cursor": {
+                    "firstBatch": first_batch,
+                    "id": 0i64,
+                    "ns": format!("shoppingcart-database.{}", collection),
+                },
+                "ok": 1.0,
+            }
+        }
+        // `mongodb-cursor-pagination` panics if counting fails, so aggregations succeed without documents.
+        "aggregate" => doc! {
+            "cursor": {
+                "firstBatch": [],
+                "id": 0i64,
+                "ns": format!("shoppingcart-database.{}", command.get_str("aggregate").unwrap_or_default()),
+            },
+            "ok": 1.0,
+        },
+        "endSessions" => doc! {"ok": 1.0},
+        _ => doc! {
+            "ok": 0.0,
+            "errmsg": format!("Command: `{}` is not supported by the fake MongoDB.", command_name),
+            "code": 59,
+            "codeName": "CommandNotFound",
+        },
+    }
+}
+
+/// Defines if a document matches a filter of equality conditions and `$elemMatch` operators on dotted paths.
+///
+/// * `document` - Document to match.
+/// * `filter` - Filter to match the document against.
+fn matches_filter(document: &Document, filter: &Document) -> bool {
+    let document = Bson::Document(document.clone());
+    filter.iter().all(|(path, condition)| {
+        let path: Vec<&str> = path.split('.').collect();
+        let values = values_at(&document, &path);
+        match condition {
+            Bson::Document(operators) if operators.contains_key("$elemMatch") => {
+                let element_filter = operators.get_document("$elemMatch").unwrap();
+                values.iter().any(|value| match value {
+                    Bson::Array(elements) => elements.iter().any(|element| match element {
+                        Bson::Document(element) => matches_filter(element, element_filter),
+                        _ => false,
+                    }),
+                    _ => false,
+                })
+            }
+            _ => values.iter().any(|value| match value {
+                Bson::Array(elements) => elements.contains(condition),
+                value => *value == condition,
+            }),
+        }
+    })
+}
+
+/// Retrieves the values at a dotted path, traversing arrays like MongoDB.
+///
+/// * `value` - Value to traverse.
+/// * `path` - Segments of the dotted path.
+fn values_at<'a>(value: &'a Bson, path: &[&str]) -> Vec<&'a Bson> {
+    match (path.split_first(), value) {
+        (None, value) => vec![value],
+        (Some((key, rest)), Bson::Document(document)) => document
+            .get(*key)
+            .map(|value| values_at(value, rest))
+            .unwrap_or_default(),
+        (Some(_), Bson::Array(elements)) => elements
+            .iter()
+            .flat_map(|element| values_at(element, path))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn shoppingcarts_requires_read_all() {
+    assert_guarded("{ shoppingcarts { __typename } }", READ_ALL).await;
+}
+
+#[tokio::test]
+async fn shoppingcarts_containing_product_variant_requires_read_all() {
+    assert_guarded(
+        r#"{ shoppingcartsContainingProductVariant(id: "$productVariant") { __typename } }"#,
+        READ_ALL,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn product_variant_cart_statistics_requires_read_all() {
+    assert_guarded(
+        r#"{ productVariantCartStatistics(id: "$productVariant") { __typename } }"#,
+        READ_ALL,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn abandoned_shoppingcarts_requires_admin() {
+    assert_guarded(
+        r#"{ abandonedShoppingcarts(inactiveSince: "2020-01-01T00:00:00Z") { __typename } }"#,
+        ADMIN,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn shoppingcart_statistics_requires_admin() {
+    assert_guarded("{ shoppingcartStatistics { __typename } }", ADMIN).await;
+}
+
+#[tokio::test]
+async fn audit_log_requires_admin() {
+    assert_guarded("{ auditLog { __typename } }", ADMIN).await;
+}
+
+#[tokio::test]
+async fn my_shoppingcart_requires_authorized_user() {
+    assert_guarded("{ myShoppingcart { __typename } }", AUTHORIZED_USER).await;
+}
+
+#[tokio::test]
+async fn shoppingcarts_shared_with_me_requires_authorized_user() {
+    assert_guarded(
+        "{ shoppingcartsSharedWithMe { __typename } }",
+        AUTHORIZED_USER,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn shoppingcart_item_is_guarded_by_owner() {
+    assert_guarded(
+        r#"{ shoppingcartItem(id: "$shoppingcartItem") { __typename } }"#,
+        OWNER_READ,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn unknown_shoppingcart_item_is_forbidden_for_buyers() {
+    assert_guarded(
+        r#"{ shoppingcartItem(id: "00000000-0000-4000-8000-000000000000") { __typename } }"#,
+        UNKNOWN_OWNER_READ,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn checkout_readiness_is_guarded_by_owner() {
+    assert_guarded(
+        r#"{ checkoutReadiness(userId: "$owner") { __typename } }"#,
+        OWNER_READ,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn user_entity_shoppingcart_is_guarded_by_owner() {
+    assert_guarded(
+        r#"{ _entities(representations: [{__typename: "User", id: "$owner"}]) { ... on User { shoppingcart { __typename } } } }"#,
+        OWNER_READ,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn shoppingcart_item_entity_is_guarded_by_owner() {
+    assert_guarded(
+        r#"{ _entities(representations: [{__typename: "ShoppingCartItem", id: "$shoppingcartItem"}]) { ... on ShoppingCartItem { __typename } } }"#,
+        OWNER_READ,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn unknown_shoppingcart_item_entity_is_forbidden_for_buyers() {
+    assert_guarded(
+        r#"{ _entities(representations: [{__typename: "ShoppingCartItem", id: "00000000-0000-4000-8000-000000000000"}]) { ... on ShoppingCartItem { __typename } } }"#,
+        UNKNOWN_OWNER_READ,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn shoppingcart_updated_is_guarded_by_owner() {
+    assert_guarded(
+        r#"subscription { shoppingcartUpdated(userId: "$owner") { __typename } }"#,
+        OWNER_READ,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn update_shoppingcart_requires_write() {
+    assert_guarded(
+        r#"mutation { updateShoppingcart(input: {id: "$owner"}) { __typename } }"#,
+        OWNER_WRITE,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn update_my_shoppingcart_requires_authorized_user() {
+    assert_guarded(
+        "mutation { updateMyShoppingcart(input: {}) { __typename } }",
+        AUTHORIZED_USER,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn create_shoppingcart_item_requires_write() {
+    assert_guarded(
+        r#"mutation { createShoppingcartItem(input: {id: "$owner", shoppingCartItem: {count: 1, productVariantId: "$productVariant"}}) { __typename } }"#,
+        OWNER_WRITE,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn create_my_shoppingcart_item_requires_authorized_user() {
+    assert_guarded(
+        r#"mutation { createMyShoppingcartItem(input: {count: 1, productVariantId: "$productVariant"}) { __typename } }"#,
+        AUTHORIZED_USER,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn update_shoppingcart_item_requires_write() {
+    assert_guarded(
+        r#"mutation { updateShoppingcartItem(input: {id: "$shoppingcartItem", count: 2}) { __typename } }"#,
+        OWNER_WRITE,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn delete_shoppingcart_item_requires_write() {
+    assert_guarded(
+        r#"mutation { deleteShoppingcartItem(id: "$shoppingcartItem") }"#,
+        OWNER_WRITE,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn apply_coupon_requires_write() {
+    assert_guarded(
+        r#"mutation { applyCoupon(userId: "$owner", code: "SUMMER") { __typename } }"#,
+        OWNER_WRITE,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn apply_my_coupon_requires_authorized_user() {
+    assert_guarded(
+        r#"mutation { applyMyCoupon(code: "SUMMER") { __typename } }"#,
+        AUTHORIZED_USER,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn remove_coupon_requires_write() {
+    assert_guarded(
+        r#"mutation { removeCoupon(userId: "$owner", code: "SUMMER") { __typename } }"#,
+        OWNER_WRITE,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn remove_my_coupon_requires_authorized_user() {
+    assert_guarded(
+        r#"mutation { removeMyCoupon(code: "SUMMER") { __typename } }"#,
+        AUTHORIZED_USER,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn invite_shoppingcart_member_excludes_members() {
+    assert_guarded(
+        r#"mutation { inviteShoppingcartMember(input: {id: "$owner", memberUserId: "$foreignBuyer", permission: VIEW}) { __typename } }"#,
+        OWNER_WRITE_EXCLUDING_MEMBERS,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn update_shoppingcart_member_excludes_members() {
+    assert_guarded(
+        r#"mutation { updateShoppingcartMember(input: {id: "$owner", memberUserId: "$viewingMember", permission: EDIT}) { __typename } }"#,
+        OWNER_WRITE_EXCLUDING_MEMBERS,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn revoke_shoppingcart_member_excludes_members() {
+    assert_guarded(
+        r#"mutation { revokeShoppingcartMember(userId: "$owner", memberUserId: "$viewingMember") { __typename } }"#,
+        OWNER_WRITE_EXCLUDING_MEMBERS,
+    )
+    .await;
+}
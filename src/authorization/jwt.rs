@@ -0,0 +1,338 @@
+use std::{env, fs, str::FromStr};
+
+use async_graphql::Result;
+use bson::Uuid;
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+
+use crate::error::ShoppingCartError;
+
+use super::{AuthorizedUserHeader, Role};
+
+/// Key a bearer JWT can be verified with.
+struct VerificationKey {
+    /// Key ID matched against the `kid` of the JWT header, matches any JWT if not set.
+    key_id: Option<String>,
+    /// Public key used to verify the signature.
+    decoding_key: DecodingKey,
+    /// Signature algorithm the key is used with.
+    algorithm: Algorithm,
+}
+
+/// Relevant claims of a bearer JWT.
+#[derive(Deserialize, Debug)]
+struct Claims {
+    /// UUID of the authenticated user.
+    sub: Uuid,
+    /// Realm roles of the authenticated user.
+    #[serde(default)]
+    realm_access: RealmAccess,
+}
+
+/// Realm access claim of a bearer JWT.
+#[derive(Deserialize, Debug, Default)]
+struct RealmAccess {
+    /// Names of the realm roles of the authenticated user.
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// Verifies bearer JWTs against configured public keys.
+pub struct JwtVerifier {
+    /// Keys a JWT can be verified with.
+    keys: Vec<VerificationKey>,
+    /// Expected `iss` claim, not validated if not set.
+    issuer: Option<String>,
+    /// Expected `aud` claim, not validated if not set.
+    audience: Option<String>,
+}
+
+impl JwtVerifier {
+    /// Reads the verification configuration from the environment.
+    ///
+    /// Returns `None` if neither `$AUTHORIZATION_JWKS_FILE` nor `$AUTHORIZATION_JWT_PUBLIC_KEY_FILE` is set, which disables JWT verification.
+    /// `$AUTHORIZATION_JWT_ALGORITHM` (default `RS256`) is used for the static public key and JWKs without algorithm.
+    /// `$AUTHORIZATION_JWT_ISSUER` and `$AUTHORIZATION_JWT_AUDIENCE` are validated if set.
+    ///
+    /// Panics if a configured file cannot be read or parsed.
+    pub fn from_env() -> Option<Self> {
+        let jwks_file = env::var("AUTHORIZATION_JWKS_FILE").ok();
+        let public_key_file = env::var("AUTHORIZATION_JWT_PUBLIC_KEY_FILE").ok();
+        if jwks_file.is_none() && public_key_file.is_none() {
+            return None;
+        }
+        let default_algorithm = match env::var("AUTHORIZATION_JWT_ALGORITHM") {
+            Ok(algorithm) => Algorithm::from_str(&algorithm)
+                .unwrap_or_else(|_| panic!("$AUTHORIZATION_JWT_ALGORITHM could not be parsed.")),
+            Err(_) => Algorithm::RS256,
+        };
+        let mut keys = Vec::new();
+        if let Some(definitely_jwks_file) = jwks_file {
+            let jwks = fs::read_to_string(&definitely_jwks_file).unwrap_or_else(|_| {
+                panic!("JWKS file: `{}` could not be read.", definitely_jwks_file)
+            });
+            let jwk_set: JwkSet = serde_json::from_str(&jwks).unwrap_or_else(|_| {
+                panic!("JWKS file: `{}` could not be parsed.", definitely_jwks_file)
+            });
+            keys.extend(
+                jwk_set
+                    .keys
+                    .iter()
+                    .filter_map(|jwk| jwk_verification_key(jwk, default_algorithm)),
+            );
+        }
+        if let Some(definitely_public_key_file) = public_key_file {
+            let public_key = fs::read(&definitely_public_key_file).unwrap_or_else(|_| {
+                panic!(
+                    "Public key file: `{}` could not be read.",
+                    definitely_public_key_file
+                )
+            });
+            let decoding_key =
+                pem_decoding_key(&public_key, default_algorithm).unwrap_or_else(|| {
+                    panic!(
+                        "Public key file: `{}` could not be parsed.",
+                        definitely_public_key_file
+                    )
+                });
+            keys.push(VerificationKey {
+                key_id: None,
+                decoding_key,
+                algorithm: default_algorithm,
+            });
+        }
+        Some(Self {
+            keys,
+            issuer: env::var("AUTHORIZATION_JWT_ISSUER").ok(),
+            audience: env::var("AUTHORIZATION_JWT_AUDIENCE").ok(),
+        })
+    }
+
+    /// Verifies a bearer JWT and maps its claims to the user it authenticates.
+    ///
+    /// * `token` - Bearer JWT to verify.
+    pub fn verify(&self, token: &str) -> Result<AuthorizedUserHeader> {
+        let invalid_token = || ShoppingCartError::Unauthenticated {
+            message: "Authentication failed. Bearer token is invalid.".to_string(),
+        };
+        let header = decode_header(token).map_err(|_| invalid_token())?;
+        let key = self
+            .keys
+            .iter()
+            .find(|key| {
+                key.algorithm == header.alg
+                    && match (&key.key_id, &header.kid) {
+                        (Some(key_id), Some(kid)) => key_id == kid,
+                        (Some(_), None) => false,
+                        (None, _) => true,
+                    }
+            })
+            .ok_or_else(invalid_token)?;
+        let mut validation = Validation::new(key.algorithm);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = decode::<Claims>(token, &key.decoding_key, &validation)
+            .map_err(|_| invalid_token())?
+            .claims;
+        let roles = claims
+            .realm_access
+            .roles
+            .into_iter()
+            .map(Role::from)
+            .collect();
+        Ok(AuthorizedUserHeader {
+            id: claims.sub,
+            roles,
+        })
+    }
+}
+
+/// Builds a verification key from a JWK, skipping JWKs not meant for signature verification.
+///
+/// * `jwk` - JWK to build the verification key from.
+/// * `default_algorithm` - Algorithm used if the JWK does not specify one.
+fn jwk_verification_key(jwk: &Jwk, default_algorithm: Algorithm) -> Option<VerificationKey> {
+    let algorithm = match jwk.common.key_algorithm {
+        Some(key_algorithm) => Algorithm::from_str(&format!("{:?}", key_algorithm)).ok()?,
+        None => default_algorithm,
+    };
+    Some(VerificationKey {
+        key_id: jwk.common.key_id.clone(),
+        decoding_key: DecodingKey::from_jwk(jwk).ok()?,
+        algorithm,
+    })
+}
+
+/// Parses a PEM encoded public key for an algorithm.
+///
+/// * `public_key` - PEM encoded public key.
+/// * `algorithm` - Algorithm the public key is used with.
+fn pem_decoding_key(public_key: &[u8], algorithm: Algorithm) -> Option<DecodingKey> {
+    match algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => DecodingKey::from_rsa_pem(public_key).ok(),
+        Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(public_key).ok(),
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(public_key).ok(),
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Key ID of the key generated for the tests.
+    const KEY_ID: &str = "test-key";
+
+    /// Expected issuer of the verifier of the tests.
+    const ISSUER: &str = "https://keycloak.misarch/realms/Misarch";
+
+    /// Expected audience of the verifier of the tests.
+    const AUDIENCE: &str = "shoppingcart";
+
+    /// Generates an Ed25519 key pair and a verifier accepting JWTs signed with it.
+    fn verifier_with_key() -> (JwtVerifier, EncodingKey) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let verifier = JwtVerifier {
+            keys: vec![VerificationKey {
+                key_id: Some(KEY_ID.to_string()),
+                decoding_key: DecodingKey::from_ed_der(key_pair.public_key().as_ref()),
+                algorithm: Algorithm::EdDSA,
+            }],
+            issuer: Some(ISSUER.to_string()),
+            audience: Some(AUDIENCE.to_string()),
+        };
+        (verifier, EncodingKey::from_ed_der(pkcs8.as_ref()))
+    }
+
+    /// Builds valid claims of a user, which tests modify to make them invalid.
+    ///
+    /// * `user_id` - UUID of the authenticated user.
+    fn claims(user_id: Uuid) -> Value {
+        json!({
+            "sub": user_id.to_string(),
+            "iss": ISSUER,
+            "aud": AUDIENCE,
+            "exp": jsonwebtoken::get_current_timestamp() + 300,
+            "realm_access": {"roles": ["buyer", "employee", "offline_access"]},
+        })
+    }
+
+    /// Signs claims with the generated key.
+    ///
+    /// * `encoding_key` - Generated key.
+    /// * `kid` - Key ID of the JWT header.
+    /// * `claims` - Claims of the JWT.
+    fn sign(encoding_key: &EncodingKey, kid: &str, claims: &Value) -> String {
+        let header = Header {
+            kid: Some(kid.to_string()),
+            ..Header::new(Algorithm::EdDSA)
+        };
+        encode(&header, claims, encoding_key).unwrap()
+    }
+
+    #[test]
+    fn verify_accepts_valid_token_and_maps_realm_roles() {
+        let (verifier, encoding_key) = verifier_with_key();
+        let user_id = Uuid::new();
+        let token = sign(&encoding_key, KEY_ID, &claims(user_id));
+        let authorized_user_header = verifier.verify(&token).unwrap();
+        assert_eq!(authorized_user_header.id(), user_id);
+        assert_eq!(
+            authorized_user_header.roles,
+            vec![
+                Role::Buyer,
+                Role::Employee,
+                Role::Other("offline_access".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_maps_missing_realm_access_to_no_roles() {
+        let (verifier, encoding_key) = verifier_with_key();
+        let mut claims = claims(Uuid::new());
+        claims.as_object_mut().unwrap().remove("realm_access");
+        let token = sign(&encoding_key, KEY_ID, &claims);
+        assert!(verifier.verify(&token).unwrap().roles.is_empty());
+    }
+
+    #[test]
+    fn verify_rejects_expired_token() {
+        let (verifier, encoding_key) = verifier_with_key();
+        let mut claims = claims(Uuid::new());
+        claims["exp"] = json!(jsonwebtoken::get_current_timestamp() - 300);
+        let token = sign(&encoding_key, KEY_ID, &claims);
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_wrong_issuer() {
+        let (verifier, encoding_key) = verifier_with_key();
+        let mut claims = claims(Uuid::new());
+        claims["iss"] = json!("https://attacker.example/realms/Misarch");
+        let token = sign(&encoding_key, KEY_ID, &claims);
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_wrong_audience() {
+        let (verifier, encoding_key) = verifier_with_key();
+        let mut claims = claims(Uuid::new());
+        claims["aud"] = json!("inventory");
+        let token = sign(&encoding_key, KEY_ID, &claims);
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_unknown_kid() {
+        let (verifier, encoding_key) = verifier_with_key();
+        let token = sign(&encoding_key, "rotated-key", &claims(Uuid::new()));
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_algorithm_mismatch() {
+        let (verifier, _) = verifier_with_key();
+        let header = Header {
+            kid: Some(KEY_ID.to_string()),
+            ..Header::new(Algorithm::HS256)
+        };
+        let token = encode(
+            &header,
+            &claims(Uuid::new()),
+            &EncodingKey::from_secret(b"shared secret"),
+        )
+        .unwrap();
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_token_signed_with_other_key() {
+        let (verifier, _) = verifier_with_key();
+        let (_, other_encoding_key) = verifier_with_key();
+        let token = sign(&other_encoding_key, KEY_ID, &claims(Uuid::new()));
+        assert!(verifier.verify(&token).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    str::FromStr,
+};
+
+use super::Role;
+
+/// Permission granted to users by their roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// Read data owned by the user.
+    ReadOwn,
+    /// Modify data owned by the user.
+    WriteOwn,
+    /// Read data of all users.
+    ReadAll,
+    /// Modify data of all users.
+    WriteAll,
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-own" => Ok(Self::ReadOwn),
+            "write-own" => Ok(Self::WriteOwn),
+            "read-all" => Ok(Self::ReadAll),
+            "write-all" => Ok(Self::WriteAll),
+            _ => Err(format!("Permission: `{}` is unknown.", s)),
+        }
+    }
+}
+
+/// Kind of access to data of a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Data is read.
+    Read,
+    /// Data is modified.
+    Write,
+}
+
+impl Access {
+    /// Permission required for this access to data owned by the user.
+    pub fn own_permission(self) -> Permission {
+        match self {
+            Self::Read => Permission::ReadOwn,
+            Self::Write => Permission::WriteOwn,
+        }
+    }
+
+    /// Permission required for this access to data of all users.
+    pub fn all_permission(self) -> Permission {
+        match self {
+            Self::Read => Permission::ReadAll,
+            Self::Write => Permission::WriteAll,
+        }
+    }
+}
+
+/// Mapping from role names to the permissions granted by the roles.
+///
+/// Roles without mapping, including unknown roles, grant no permissions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RolePermissions(HashMap<String, HashSet<Permission>>);
+
+impl Default for RolePermissions {
+    fn default() -> Self {
+        Self::from_str(
+            "buyer=read-own,write-own;admin=read-all,write-all;employee=read-all,write-all",
+        )
+        .unwrap()
+    }
+}
+
+impl FromStr for RolePermissions {
+    type Err = String;
+
+    /// Parses a mapping of the form `role=permission,permission;role=permission`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(';')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let (role, permissions) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("Role permission entry: `{}` is invalid.", entry))?;
+                let permissions = permissions
+                    .split(',')
+                    .filter(|permission| !permission.trim().is_empty())
+                    .map(|permission| Permission::from_str(permission.trim()))
+                    .collect::<Result<HashSet<Permission>, String>>()?;
+                Ok((role.trim().to_string(), permissions))
+            })
+            .collect::<Result<HashMap<String, HashSet<Permission>>, String>>()
+            .map(Self)
+    }
+}
+
+impl RolePermissions {
+    /// Reads the mapping from `$ROLE_PERMISSIONS`, falling back to the default mapping if it is not set.
+    ///
+    /// Panics if `$ROLE_PERMISSIONS` is set but cannot be parsed.
+    pub fn from_env() -> Self {
+        match env::var("ROLE_PERMISSIONS") {
+            Ok(role_permissions) => Self::from_str(&role_permissions)
+                .unwrap_or_else(|error| panic!("$ROLE_PERMISSIONS could not be parsed. {}", error)),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Defines if any of the roles grants a permission.
+    ///
+    /// * `roles` - Roles of a user.
+    /// * `permission` - Permission to check.
+    pub fn grants(&self, roles: &[Role], permission: Permission) -> bool {
+        roles.iter().any(|role| {
+            self.0
+                .get(role.name())
+                .is_some_and(|permissions| permissions.contains(&permission))
+        })
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt::Display;
+
+use async_graphql::{Error, ErrorExtensions};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use bson::Uuid;
+use log::{error, warn};
+
+/// Error of the shopping cart service.
+///
+/// Converted into a GraphQL error with a stable `code` extension and structured fields, so clients do not need to parse messages.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShoppingCartError {
+    /// Requested entity does not exist, code `NOT_FOUND`.
+    NotFound {
+        /// Human readable description of the error.
+        message: String,
+        /// UUID of the entity which does not exist.
+        id: Option<Uuid>,
+    },
+    /// Authenticated user is not permitted to perform the operation, code `FORBIDDEN`.
+    Forbidden {
+        /// Human readable description of the error.
+        message: String,
+        /// UUID of the authenticated user.
+        user_id: Option<Uuid>,
+    },
+    /// User of the request could not be authenticated, code `UNAUTHENTICATED`.
+    Unauthenticated {
+        /// Human readable description of the error.
+        message: String,
+    },
+    /// Input of the operation is invalid, code `VALIDATION`.
+    Validation {
+        /// Human readable description of the error.
+        message: String,
+        /// UUID of the entity the invalid input refers to.
+        id: Option<Uuid>,
+    },
+    /// Operation conflicts with the current state, code `CONFLICT`.
+    Conflict {
+        /// Human readable description of the error.
+        message: String,
+        /// UUID of the entity the operation conflicts with.
+        id: Option<Uuid>,
+    },
+    /// Reading from or writing to MongoDB failed, code `STORAGE`.
+    ///
+    /// Distinct from `NotFound`, as the operation might succeed when retried.
+    Storage {
+        /// Human readable description of the error.
+        message: String,
+    },
+    /// User exceeded its rate limit, code `RATE_LIMITED`.
+    RateLimited {
+        /// Human readable description of the error.
+        message: String,
+        /// UUID of the rate limited user.
+        user_id: Uuid,
+        /// Seconds after which the user can retry.
+        retry_after: u64,
+    },
+}
+
+impl ShoppingCartError {
+    /// Creates a storage error and logs it including the underlying MongoDB error, which is not exposed to clients.
+    ///
+    /// * `message` - Human readable description of the failed operation.
+    /// * `error` - Underlying error of MongoDB.
+    pub fn storage(message: impl Into<String>, error: impl Display) -> Self {
+        let message = message.into();
+        error!("{} Cause: {}", message, error);
+        Self::Storage { message }
+    }
+
+    /// Defines if the operation might succeed when retried, which is the case for storage errors and exceeded rate limits.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Storage { .. } | Self::RateLimited { .. })
+    }
+
+    /// Stable code of the error, set as `code` extension.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound { .. } => "NOT_FOUND",
+            Self::Forbidden { .. } => "FORBIDDEN",
+            Self::Unauthenticated { .. } => "UNAUTHENTICATED",
+            Self::Validation { .. } => "VALIDATION",
+            Self::Conflict { .. } => "CONFLICT",
+            Self::Storage { .. } => "STORAGE",
+            Self::RateLimited { .. } => "RATE_LIMITED",
+        }
+    }
+
+    /// Human readable description of the error.
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound { message, .. }
+            | Self::Forbidden { message, .. }
+            | Self::Unauthenticated { message }
+            | Self::Validation { message, .. }
+            | Self::Conflict { message, .. }
+            | Self::Storage { message }
+            | Self::RateLimited { message, .. } => message,
+        }
+    }
+}
+
+impl ErrorExtensions for ShoppingCartError {
+    fn extend(&self) -> Error {
+        Error::new(self.message()).extend_with(|_, extensions| {
+            extensions.set("code", self.code());
+            extensions.set("retryable", self.is_retryable());
+            match self {
+                Self::NotFound { id: Some(id), .. }
+                | Self::Validation { id: Some(id), .. }
+                | Self::Conflict { id: Some(id), .. } => extensions.set("id", id.to_string()),
+                Self::Forbidden {
+                    user_id: Some(user_id),
+                    ..
+                } => extensions.set("userId", user_id.to_string()),
+                Self::RateLimited {
+                    user_id,
+                    retry_after,
+                    ..
+                } => {
+                    extensions.set("userId", user_id.to_string());
+                    extensions.set("retryAfter", *retry_after);
+                }
+                _ => {}
+            }
+        })
+    }
+}
+
+/// Implements conversion to an HTTP response for event handlers, following the retry semantics of Dapr.
+///
+/// Dapr retries events answered with `500 Internal Server Error` and drops events answered with `404 Not Found`.
+/// Only retryable errors are retried, as other errors would fail again on every redelivery.
+impl IntoResponse for ShoppingCartError {
+    fn into_response(self) -> Response {
+        match self.is_retryable() {
+            true => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            false => {
+                warn!("Dropping event. {}", self.message());
+                StatusCode::NOT_FOUND.into_response()
+            }
+        }
+    }
+}
+
+/// Implements conversion to a GraphQL error including the extensions, so `?` keeps the code.
+impl From<ShoppingCartError> for Error {
+    fn from(value: ShoppingCartError) -> Self {
+        value.extend()
+    }
+}
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use bson::{doc, serde_helpers::bson_datetime_as_rfc3339_string, DateTime, Uuid};
+use log::{info, warn};
+use mongodb::Collection;
+use serde::Serialize;
+
+use crate::graphql::{
+    limits::env_var_or,
+    model::{filter_datatypes::abandoned_shoppingcarts_filter, user::User},
+};
+
+use super::dapr_publisher::DaprPublisher;
+
+/// Topic abandoned shopping carts are published to.
+const ABANDONED_SHOPPINGCART_TOPIC: &str = "shoppingcart/shoppingcart/abandoned";
+
+/// Configuration of the background job detecting abandoned shopping carts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AbandonedShoppingCartJobConfig {
+    /// Duration without updates after which a non-empty shopping cart is abandoned.
+    pub inactivity_window: Duration,
+    /// Duration between two checks for abandoned shopping carts.
+    pub check_interval: Duration,
+}
+
+impl AbandonedShoppingCartJobConfig {
+    /// Reads the configuration from the environment, falling back to defaults for unset variables.
+    ///
+    /// Uses `$SHOPPINGCART_ABANDONMENT_INACTIVITY_SECONDS` (default one day) and `$SHOPPINGCART_ABANDONMENT_CHECK_INTERVAL_SECONDS` (default one hour).
+    /// Returns a description of the first variable which is set but cannot be parsed.
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            inactivity_window: Duration::from_secs(env_var_or(
+                "SHOPPINGCART_ABANDONMENT_INACTIVITY_SECONDS",
+                86400,
+            )?),
+            check_interval: Duration::from_secs(env_var_or(
+                "SHOPPINGCART_ABANDONMENT_CHECK_INTERVAL_SECONDS",
+                3600,
+            )?),
+        })
+    }
+}
+
+/// Event data of an abandoned shopping cart.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AbandonedShoppingCartEventData {
+    /// UUID of user owning the shopping cart.
+    pub user_id: Uuid,
+    /// Timestamp when the shopping cart was last updated.
+    #[serde(with = "bson_datetime_as_rfc3339_string")]
+    pub last_updated_at: DateTime,
+    /// UUIDs of the shopping cart items in the shopping cart.
+    pub shoppingcart_item_ids: Vec<Uuid>,
+}
+
+/// Periodically publishes shopping carts which became abandoned.
+///
+/// Runs until the service stops.
+///
+/// * `collection` - MongoDB collection of users.
+/// * `publisher` - Publisher for the Dapr sidecar.
+/// * `config` - Configuration of the job.
+pub async fn run_abandoned_shoppingcart_job(
+    collection: Collection<User>,
+    publisher: DaprPublisher,
+    config: AbandonedShoppingCartJobConfig,
+) {
+    let mut interval = tokio::time::interval(config.check_interval);
+    loop {
+        interval.tick().await;
+        if let Err(error) = publish_abandoned_shoppingcarts(&collection, &publisher, &config).await
+        {
+            warn!("Checking for abandoned shoppingcarts failed: {}", error);
+        }
+    }
+}
+
+/// Publishes all shopping carts which became abandoned since their abandonment was last published.
+///
+/// A shopping cart is published at most once per inactivity window, it is published again only after it was updated.
+/// Each shopping cart is claimed atomically before publishing, so replicas running the job concurrently do not publish it twice.
+/// Stops at the first failed publication and releases its claim, so it is retried in the next check.
+///
+/// * `collection` - MongoDB collection of users.
+/// * `publisher` - Publisher for the Dapr sidecar.
+/// * `config` - Configuration of the job.
+async fn publish_abandoned_shoppingcarts(
+    collection: &Collection<User>,
+    publisher: &DaprPublisher,
+    config: &AbandonedShoppingCartJobConfig,
+) -> mongodb::error::Result<()> {
+    let inactivity_window_millis =
+        i64::try_from(config.inactivity_window.as_millis()).unwrap_or(i64::MAX);
+    let inactive_since = DateTime::from_millis(
+        DateTime::now()
+            .timestamp_millis()
+            .saturating_sub(inactivity_window_millis),
+    );
+    let mut filter = abandoned_shoppingcarts_filter(inactive_since, 1);
+    filter.insert(
+        "$or",
+        vec![
+            doc! {"shoppingcart.abandonment_published_at": {"$exists": false}},
+            doc! {"$expr": {"$lt": ["$shoppingcart.abandonment_published_at", "$shoppingcart.last_updated_at"]}},
+        ],
+    );
+    loop {
+        let claimed_at = DateTime::now();
+        let maybe_user = collection
+            .find_one_and_update(
+                filter.clone(),
+                doc! {"$set": {"shoppingcart.abandonment_published_at": claimed_at}},
+                None,
+            )
+            .await?;
+        let Some(user) = maybe_user else {
+            return Ok(());
+        };
+        let event_data = AbandonedShoppingCartEventData {
+            user_id: user._id,
+            last_updated_at: user.shoppingcart.last_updated_at,
+            shoppingcart_item_ids: user
+                .shoppingcart
+                .internal_shoppingcart_items
+                .iter()
+                .map(|shoppingcart_item| shoppingcart_item._id)
+                .collect(),
+        };
+        match publisher
+            .publish(ABANDONED_SHOPPINGCART_TOPIC, &event_data)
+            .await
+        {
+            Ok(()) => info!("Published abandoned shoppingcart of user: `{}`.", user._id),
+            Err(error) => {
+                warn!(
+                    "Publishing abandoned shoppingcart of user: `{}` failed: {}",
+                    user._id, error
+                );
+                release_claim(collection, &user, claimed_at).await?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Restores the previous abandonment publication timestamp of a shopping cart after its publication failed.
+///
+/// Leaves the shopping cart untouched if it was claimed again in the meantime.
+///
+/// * `collection` - MongoDB collection of users.
+/// * `user` - User owning the shopping cart, as returned by the claim before it was updated.
+/// * `claimed_at` - Timestamp the shopping cart was claimed with.
+async fn release_claim(
+    collection: &Collection<User>,
+    user: &User,
+    claimed_at: DateTime,
+) -> mongodb::error::Result<()> {
+    let update = match user.shoppingcart.abandonment_published_at {
+        Some(abandonment_published_at) => {
+            doc! {"$set": {"shoppingcart.abandonment_published_at": abandonment_published_at}}
+        }
+        None => doc! {"$unset": {"shoppingcart.abandonment_published_at": ""}},
+    };
+    collection
+        .update_one(
+            doc! {"_id": user._id, "shoppingcart.abandonment_published_at": claimed_at},
+            update,
+            None,
+        )
+        .await?;
+    Ok(())
+}
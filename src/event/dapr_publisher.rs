@@ -0,0 +1,48 @@
+use std::env;
+
+use log::info;
+use serde::Serialize;
+
+/// Name of the Dapr pub/sub component events are published to.
+const PUBSUB_NAME: &str = "pubsub";
+
+/// Publishes events over the HTTP API of the Dapr sidecar.
+#[derive(Clone)]
+pub struct DaprPublisher {
+    /// HTTP client used for requests to the Dapr sidecar.
+    client: reqwest::Client,
+    /// Base URL of the Dapr sidecar.
+    base_url: String,
+}
+
+impl DaprPublisher {
+    /// Creates a publisher for the Dapr sidecar listening on `$DAPR_HTTP_PORT`, defaulting to `3500`.
+    pub fn from_env() -> Self {
+        let port = env::var("DAPR_HTTP_PORT").unwrap_or_else(|_| "3500".to_string());
+        Self {
+            client: reqwest::Client::new(),
+            base_url: format!("http://localhost:{}", port),
+        }
+    }
+
+    /// Publishes an event to a topic.
+    ///
+    /// * `topic` - Topic to publish the event to.
+    /// * `data` - Event data, sent as JSON.
+    pub async fn publish<T: Serialize>(&self, topic: &str, data: &T) -> Result<(), reqwest::Error> {
+        let url = format!(
+            "{}/v1.0/publish/{}/{}",
+            self.base_url,
+            PUBSUB_NAME,
+            topic.replace('/', "%2F")
+        );
+        self.client
+            .post(url)
+            .json(data)
+            .send()
+            .await?
+            .error_for_status()?;
+        info!("Published event to topic: `{}`.", topic);
+        Ok(())
+    }
+}
@@ -1,13 +1,29 @@
+use std::fmt::Display;
+
 use axum::{debug_handler, extract::State, http::StatusCode, Json};
-use bson::{doc, Uuid};
+use bson::{
+    doc, serde_helpers::bson_datetime_as_rfc3339_string, to_bson, Bson, DateTime, Document, Uuid,
+};
 use log::info;
-use mongodb::Collection;
+use mongodb::{
+    error::{Error, ErrorKind, WriteFailure},
+    options::{ReplaceOptions, UpdateOptions},
+    Collection,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::graphql::model::{
-    foreign_types::ProductVariant, shoppingcart::ShoppingCart, user::User,
+use crate::{
+    error::ShoppingCartError,
+    graphql::model::{
+        foreign_types::{Discount, ProductVariant, ProductVariantVersion, TaxRate, TaxRateVersion},
+        shoppingcart::ShoppingCart,
+        user::User,
+    },
 };
 
+/// MongoDB error code of a write violating a unique index.
+const DUPLICATE_KEY_ERROR_CODE: i32 = 11000;
+
 /// Data to send to Dapr in order to describe a subscription.
 #[derive(Serialize)]
 pub struct Pubsub {
@@ -65,6 +81,85 @@ pub struct OrderItemEventData {
     pub count: u64,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Relevant part of product variant version creation event data.
+pub struct ProductVariantVersionEventData {
+    /// Product variant version UUID.
+    pub id: Uuid,
+    /// Version number of the product variant version.
+    pub version: u32,
+    /// Retail price of the product variant version.
+    pub retail_price: u32,
+    /// UUID of tax rate associated with the product variant version.
+    pub tax_rate_id: Uuid,
+    /// UUID of product variant associated with the product variant version.
+    pub product_variant_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Relevant part of tax rate version creation event data.
+pub struct TaxRateVersionEventData {
+    /// Tax rate version UUID.
+    pub id: Uuid,
+    /// Version number of the tax rate version.
+    pub version: u32,
+    /// Rate of the tax rate version.
+    pub rate: f64,
+    /// UUID of tax rate associated with the tax rate version.
+    pub tax_rate_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Relevant part of discount creation event data.
+pub struct DiscountEventData {
+    /// Discount UUID.
+    pub id: Uuid,
+    /// Coupon code used to apply the discount.
+    pub code: String,
+    /// Relative discount.
+    pub discount: f64,
+    /// Timestamp from which the discount is valid.
+    #[serde(with = "bson_datetime_as_rfc3339_string")]
+    pub valid_from: DateTime,
+    /// Timestamp until which the discount is valid.
+    #[serde(with = "bson_datetime_as_rfc3339_string")]
+    pub valid_until: DateTime,
+    /// UUID of product variant the discount is restricted to.
+    pub product_variant_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Relevant part of product variant update event data.
+pub struct ProductVariantEventData {
+    /// Product variant UUID.
+    pub id: Uuid,
+    /// Whether the product variant is publicly visible.
+    pub is_publicly_visible: bool,
+    /// Minimum count of the product variant in a shopping cart item.
+    #[serde(default)]
+    pub min_count: Option<u32>,
+    /// Maximum count of the product variant in a shopping cart item.
+    #[serde(default)]
+    pub max_count: Option<u32>,
+    /// Step the count of the product variant in a shopping cart item has to be a multiple of.
+    #[serde(default)]
+    pub count_step: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+/// Relevant part of product variant stock update event data.
+pub struct ProductVariantStockEventData {
+    /// UUID of product variant whose stock changed.
+    pub product_variant_id: Uuid,
+    /// Amount of product items in stock.
+    pub stock: u64,
+}
+
 /// HTTP endpoint to receive events.
 ///
 /// * `state` - Service state containing database connections.
@@ -73,6 +168,8 @@ pub struct OrderItemEventData {
 pub struct HttpEventServiceState {
     pub product_variant_collection: Collection<ProductVariant>,
     pub user_collection: Collection<User>,
+    pub tax_rate_collection: Collection<TaxRate>,
+    pub discount_collection: Collection<Discount>,
 }
 
 /// HTTP endpoint to list topic subsciptions.
@@ -92,10 +189,52 @@ pub async fn list_topic_subscriptions() -> Result<Json<Vec<Pubsub>>, StatusCode>
         topic: "order/order/created".to_string(),
         route: "/on-order-creation-event".to_string(),
     };
+    let pubsub_product_variant_version = Pubsub {
+        pubsubname: "pubsub".to_string(),
+        topic: "catalog/product-variant-version/created".to_string(),
+        route: "/on-product-variant-version-creation-event".to_string(),
+    };
+    let pubsub_tax_rate_version = Pubsub {
+        pubsubname: "pubsub".to_string(),
+        topic: "tax/tax-rate-version/created".to_string(),
+        route: "/on-tax-rate-version-creation-event".to_string(),
+    };
+    let pubsub_discount = Pubsub {
+        pubsubname: "pubsub".to_string(),
+        topic: "discount/discount/created".to_string(),
+        route: "/on-discount-creation-event".to_string(),
+    };
+    let pubsub_discount_update = Pubsub {
+        pubsubname: "pubsub".to_string(),
+        topic: "discount/discount/updated".to_string(),
+        route: "/on-discount-update-event".to_string(),
+    };
+    let pubsub_discount_deletion = Pubsub {
+        pubsubname: "pubsub".to_string(),
+        topic: "discount/discount/deleted".to_string(),
+        route: "/on-discount-deletion-event".to_string(),
+    };
+    let pubsub_product_variant_update = Pubsub {
+        pubsubname: "pubsub".to_string(),
+        topic: "catalog/product-variant/updated".to_string(),
+        route: "/on-product-variant-update-event".to_string(),
+    };
+    let pubsub_product_variant_stock = Pubsub {
+        pubsubname: "pubsub".to_string(),
+        topic: "inventory/product-variant/stock-updated".to_string(),
+        route: "/on-product-variant-stock-update-event".to_string(),
+    };
     Ok(Json(vec![
         pubsub_user,
         pubsub_product_variant,
         pubsub_order,
+        pubsub_product_variant_version,
+        pubsub_tax_rate_version,
+        pubsub_discount,
+        pubsub_discount_update,
+        pubsub_discount_deletion,
+        pubsub_product_variant_update,
+        pubsub_product_variant_stock,
     ]))
 }
 
@@ -107,7 +246,7 @@ pub async fn list_topic_subscriptions() -> Result<Json<Vec<Pubsub>>, StatusCode>
 pub async fn on_topic_event(
     State(state): State<HttpEventServiceState>,
     Json(event): Json<Event<EventData>>,
-) -> Result<Json<TopicEventResponse>, StatusCode> {
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
     info!("{:?}", event);
 
     match event.topic.as_str() {
@@ -115,7 +254,7 @@ pub async fn on_topic_event(
             add_product_variant_to_mongodb(state.product_variant_collection, event.data.id).await?
         }
         "user/user/created" => add_user_to_mongodb(state.user_collection, event.data.id).await?,
-        _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        _ => return Err(unhandled_topic(&event.topic)),
     }
     Ok(Json(TopicEventResponse::default()))
 }
@@ -128,14 +267,156 @@ pub async fn on_topic_event(
 pub async fn on_order_creation_event(
     State(state): State<HttpEventServiceState>,
     Json(event): Json<Event<OrderEventData>>,
-) -> Result<Json<TopicEventResponse>, StatusCode> {
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
     info!("{:?}", event);
 
     match event.topic.as_str() {
         "order/order/created" => {
             delete_ordered_shoppingcart_items_in_mongodb(&state.user_collection, event.data).await?
         }
-        _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        _ => return Err(unhandled_topic(&event.topic)),
+    }
+    Ok(Json(TopicEventResponse::default()))
+}
+
+/// HTTP endpoint to receive product variant version creation events.
+///
+/// * `state` - Service state containing database connections.
+/// * `event` - Event handled by endpoint.
+#[debug_handler(state = HttpEventServiceState)]
+pub async fn on_product_variant_version_creation_event(
+    State(state): State<HttpEventServiceState>,
+    Json(event): Json<Event<ProductVariantVersionEventData>>,
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
+    info!("{:?}", event);
+
+    match event.topic.as_str() {
+        "catalog/product-variant-version/created" => {
+            update_product_variant_version_in_mongodb(&state.product_variant_collection, event.data)
+                .await?
+        }
+        _ => return Err(unhandled_topic(&event.topic)),
+    }
+    Ok(Json(TopicEventResponse::default()))
+}
+
+/// HTTP endpoint to receive tax rate version creation events.
+///
+/// * `state` - Service state containing database connections.
+/// * `event` - Event handled by endpoint.
+#[debug_handler(state = HttpEventServiceState)]
+pub async fn on_tax_rate_version_creation_event(
+    State(state): State<HttpEventServiceState>,
+    Json(event): Json<Event<TaxRateVersionEventData>>,
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
+    info!("{:?}", event);
+
+    match event.topic.as_str() {
+        "tax/tax-rate-version/created" => {
+            update_tax_rate_version_in_mongodb(&state.tax_rate_collection, event.data).await?
+        }
+        _ => return Err(unhandled_topic(&event.topic)),
+    }
+    Ok(Json(TopicEventResponse::default()))
+}
+
+/// HTTP endpoint to receive discount creation events.
+///
+/// * `state` - Service state containing database connections.
+/// * `event` - Event handled by endpoint.
+#[debug_handler(state = HttpEventServiceState)]
+pub async fn on_discount_creation_event(
+    State(state): State<HttpEventServiceState>,
+    Json(event): Json<Event<DiscountEventData>>,
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
+    info!("{:?}", event);
+
+    match event.topic.as_str() {
+        "discount/discount/created" => {
+            add_discount_to_mongodb(&state.discount_collection, event.data).await?
+        }
+        _ => return Err(unhandled_topic(&event.topic)),
+    }
+    Ok(Json(TopicEventResponse::default()))
+}
+
+/// HTTP endpoint to receive discount update events.
+///
+/// * `state` - Service state containing database connections.
+/// * `event` - Event handled by endpoint.
+#[debug_handler(state = HttpEventServiceState)]
+pub async fn on_discount_update_event(
+    State(state): State<HttpEventServiceState>,
+    Json(event): Json<Event<DiscountEventData>>,
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
+    info!("{:?}", event);
+
+    match event.topic.as_str() {
+        "discount/discount/updated" => {
+            update_discount_in_mongodb(&state.discount_collection, event.data).await?
+        }
+        _ => return Err(unhandled_topic(&event.topic)),
+    }
+    Ok(Json(TopicEventResponse::default()))
+}
+
+/// HTTP endpoint to receive discount deletion events.
+///
+/// * `state` - Service state containing database connections.
+/// * `event` - Event handled by endpoint.
+#[debug_handler(state = HttpEventServiceState)]
+pub async fn on_discount_deletion_event(
+    State(state): State<HttpEventServiceState>,
+    Json(event): Json<Event<EventData>>,
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
+    info!("{:?}", event);
+
+    match event.topic.as_str() {
+        "discount/discount/deleted" => {
+            delete_discount_in_mongodb(&state.discount_collection, event.data.id).await?
+        }
+        _ => return Err(unhandled_topic(&event.topic)),
+    }
+    Ok(Json(TopicEventResponse::default()))
+}
+
+/// HTTP endpoint to receive product variant update events.
+///
+/// * `state` - Service state containing database connections.
+/// * `event` - Event handled by endpoint.
+#[debug_handler(state = HttpEventServiceState)]
+pub async fn on_product_variant_update_event(
+    State(state): State<HttpEventServiceState>,
+    Json(event): Json<Event<ProductVariantEventData>>,
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
+    info!("{:?}", event);
+
+    match event.topic.as_str() {
+        "catalog/product-variant/updated" => {
+            update_product_variant_in_mongodb(&state.product_variant_collection, event.data).await?
+        }
+        _ => return Err(unhandled_topic(&event.topic)),
+    }
+    Ok(Json(TopicEventResponse::default()))
+}
+
+/// HTTP endpoint to receive product variant stock update events.
+///
+/// * `state` - Service state containing database connections.
+/// * `event` - Event handled by endpoint.
+#[debug_handler(state = HttpEventServiceState)]
+pub async fn on_product_variant_stock_update_event(
+    State(state): State<HttpEventServiceState>,
+    Json(event): Json<Event<ProductVariantStockEventData>>,
+) -> Result<Json<TopicEventResponse>, ShoppingCartError> {
+    info!("{:?}", event);
+
+    match event.topic.as_str() {
+        "inventory/product-variant/stock-updated" => {
+            update_product_variant_stock_in_mongodb(&state.product_variant_collection, event.data)
+                .await?
+        }
+        _ => return Err(unhandled_topic(&event.topic)),
     }
     Ok(Json(TopicEventResponse::default()))
 }
@@ -147,7 +428,7 @@ pub async fn on_order_creation_event(
 pub async fn delete_ordered_shoppingcart_items_in_mongodb(
     collection: &Collection<User>,
     order_event_data: OrderEventData,
-) -> Result<(), StatusCode> {
+) -> Result<(), ShoppingCartError> {
     let shoppingcart_item_ids: Vec<Uuid> = order_event_data
         .order_items
         .iter()
@@ -168,22 +449,43 @@ pub async fn delete_ordered_shoppingcart_items_in_mongodb(
         .await
     {
         Ok(_) => Ok(()),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(error) => {
+            let message = format!(
+                "Removing ordered shoppingcart items of user of UUID: `{}` failed in MongoDB.",
+                order_event_data.user_id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
     }
 }
 
 /// Add a newly created product variant to MongoDB.
 ///
+/// Leaves an already projected product variant untouched, as version events might be delivered before the creation event.
+///
 /// * `collection` - MongoDB collection to add newly created product variant to.
 /// * `id` - UUID of newly created product variant.
 pub async fn add_product_variant_to_mongodb(
     collection: Collection<ProductVariant>,
     id: Uuid,
-) -> Result<(), StatusCode> {
-    let product_variant = ProductVariant { _id: id };
-    match collection.insert_one(product_variant, None).await {
+) -> Result<(), ShoppingCartError> {
+    let options = UpdateOptions::builder().upsert(true).build();
+    match collection
+        .update_one(
+            doc! {"_id": id },
+            doc! {"$setOnInsert": {"_id": id }},
+            options,
+        )
+        .await
+    {
         Ok(_) => Ok(()),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(error) => {
+            let message = format!(
+                "Adding product variant of UUID: `{}` failed in MongoDB.",
+                id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
     }
 }
 
@@ -191,13 +493,296 @@ pub async fn add_product_variant_to_mongodb(
 ///
 /// * `collection` - MongoDB collection to add newly created user to.
 /// * `id` - UUID of newly created user.
-pub async fn add_user_to_mongodb(collection: Collection<User>, id: Uuid) -> Result<(), StatusCode> {
+pub async fn add_user_to_mongodb(
+    collection: Collection<User>,
+    id: Uuid,
+) -> Result<(), ShoppingCartError> {
     let user = User {
         _id: id,
         shoppingcart: ShoppingCart::new(),
     };
     match collection.insert_one(user, None).await {
         Ok(_) => Ok(()),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(error) if is_duplicate_key_error(&error) => Ok(()),
+        Err(error) => {
+            let message = format!("Adding user of UUID: `{}` failed in MongoDB.", id);
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Add a newly created discount to MongoDB.
+///
+/// * `collection` - MongoDB collection to add newly created discount to.
+/// * `discount_event_data` - Discount creation event data.
+pub async fn add_discount_to_mongodb(
+    collection: &Collection<Discount>,
+    discount_event_data: DiscountEventData,
+) -> Result<(), ShoppingCartError> {
+    let discount = Discount {
+        _id: discount_event_data.id,
+        code: discount_event_data.code,
+        discount: discount_event_data.discount,
+        valid_from: discount_event_data.valid_from,
+        valid_until: discount_event_data.valid_until,
+        product_variant_id: discount_event_data.product_variant_id,
+    };
+    match collection.insert_one(discount, None).await {
+        Ok(_) => Ok(()),
+        Err(error) if is_duplicate_key_error(&error) => Ok(()),
+        Err(error) => {
+            let message = format!(
+                "Adding discount of UUID: `{}` failed in MongoDB.",
+                discount_event_data.id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Updates the visibility and quantity rules of a product variant in MongoDB, creating the product variant if it is not present yet.
+///
+/// Only sets the quantity rules contained in the event, so events without quantity rules keep the projected ones.
+///
+/// * `collection` - MongoDB collection to update product variant in.
+/// * `product_variant_event_data` - Product variant update event data.
+pub async fn update_product_variant_in_mongodb(
+    collection: &Collection<ProductVariant>,
+    product_variant_event_data: ProductVariantEventData,
+) -> Result<(), ShoppingCartError> {
+    let mut set_doc = doc! {"is_publicly_visible": product_variant_event_data.is_publicly_visible};
+    let quantity_rules = [
+        ("min_count", product_variant_event_data.min_count),
+        ("max_count", product_variant_event_data.max_count),
+        ("count_step", product_variant_event_data.count_step),
+    ];
+    for (field, maybe_value) in quantity_rules {
+        if let Some(value) = maybe_value {
+            set_doc.insert(field, value);
+        }
+    }
+    let options = UpdateOptions::builder().upsert(true).build();
+    match collection
+        .update_one(
+            doc! {"_id": product_variant_event_data.id },
+            doc! {"$set": set_doc},
+            options,
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            let message = format!(
+                "Updating product variant of UUID: `{}` failed in MongoDB.",
+                product_variant_event_data.id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Updates the stock of a product variant in MongoDB.
+///
+/// * `collection` - MongoDB collection to update product variant in.
+/// * `product_variant_stock_event_data` - Product variant stock update event data.
+pub async fn update_product_variant_stock_in_mongodb(
+    collection: &Collection<ProductVariant>,
+    product_variant_stock_event_data: ProductVariantStockEventData,
+) -> Result<(), ShoppingCartError> {
+    match collection
+        .update_one(
+            doc! {"_id": product_variant_stock_event_data.product_variant_id },
+            doc! {"$set": {"stock": product_variant_stock_event_data.stock as i64}},
+            None,
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            let message = format!(
+                "Updating stock of product variant of UUID: `{}` failed in MongoDB.",
+                product_variant_stock_event_data.product_variant_id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Replaces a discount in MongoDB, creating the discount if it is not present yet.
+///
+/// Coupons applied to shopping carts are validated against the updated discount from then on.
+///
+/// * `collection` - MongoDB collection to update discount in.
+/// * `discount_event_data` - Discount update event data.
+pub async fn update_discount_in_mongodb(
+    collection: &Collection<Discount>,
+    discount_event_data: DiscountEventData,
+) -> Result<(), ShoppingCartError> {
+    let discount = Discount {
+        _id: discount_event_data.id,
+        code: discount_event_data.code,
+        discount: discount_event_data.discount,
+        valid_from: discount_event_data.valid_from,
+        valid_until: discount_event_data.valid_until,
+        product_variant_id: discount_event_data.product_variant_id,
+    };
+    let options = ReplaceOptions::builder().upsert(true).build();
+    match collection
+        .replace_one(doc! {"_id": discount_event_data.id }, discount, options)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            let message = format!(
+                "Updating discount of UUID: `{}` failed in MongoDB.",
+                discount_event_data.id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Deletes a discount in MongoDB.
+///
+/// Coupons of the deleted discount stay applied to shopping carts, but no longer grant a discount.
+///
+/// * `collection` - MongoDB collection to delete discount in.
+/// * `id` - UUID of deleted discount.
+pub async fn delete_discount_in_mongodb(
+    collection: &Collection<Discount>,
+    id: Uuid,
+) -> Result<(), ShoppingCartError> {
+    match collection.delete_one(doc! {"_id": id }, None).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            let message = format!("Deleting discount of UUID: `{}` failed in MongoDB.", id);
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Sets the current version of a product variant in MongoDB, creating the product variant if it is not present yet.
+///
+/// Versions older than the already projected version are ignored, as events might be delivered out of order.
+///
+/// * `collection` - MongoDB collection to update product variant in.
+/// * `product_variant_version_event_data` - Product variant version creation event data.
+pub async fn update_product_variant_version_in_mongodb(
+    collection: &Collection<ProductVariant>,
+    product_variant_version_event_data: ProductVariantVersionEventData,
+) -> Result<(), ShoppingCartError> {
+    let product_variant_version = ProductVariantVersion {
+        _id: product_variant_version_event_data.id,
+        version: product_variant_version_event_data.version,
+        retail_price: product_variant_version_event_data.retail_price,
+        tax_rate_id: product_variant_version_event_data.tax_rate_id,
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+    match collection
+        .update_one(
+            doc! {"_id": product_variant_version_event_data.product_variant_id },
+            vec![doc! {"$set": {
+                "current_version": newer_version_expression(
+                    product_variant_version.version,
+                    to_bson(&product_variant_version)
+                        .map_err(invalid_event_data)?,
+                )
+            }}],
+            options,
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            let message = format!(
+                "Updating version of product variant of UUID: `{}` failed in MongoDB.",
+                product_variant_version_event_data.product_variant_id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Sets the current version of a tax rate in MongoDB, creating the tax rate if it is not present yet.
+///
+/// Versions older than the already projected version are ignored, as events might be delivered out of order.
+///
+/// * `collection` - MongoDB collection to update tax rate in.
+/// * `tax_rate_version_event_data` - Tax rate version creation event data.
+pub async fn update_tax_rate_version_in_mongodb(
+    collection: &Collection<TaxRate>,
+    tax_rate_version_event_data: TaxRateVersionEventData,
+) -> Result<(), ShoppingCartError> {
+    let tax_rate_version = TaxRateVersion {
+        _id: tax_rate_version_event_data.id,
+        version: tax_rate_version_event_data.version,
+        rate: tax_rate_version_event_data.rate,
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+    match collection
+        .update_one(
+            doc! {"_id": tax_rate_version_event_data.tax_rate_id },
+            vec![doc! {"$set": {
+                "current_version": newer_version_expression(
+                    tax_rate_version.version,
+                    to_bson(&tax_rate_version)
+                        .map_err(invalid_event_data)?,
+                )
+            }}],
+            options,
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            let message = format!(
+                "Updating version of tax rate of UUID: `{}` failed in MongoDB.",
+                tax_rate_version_event_data.tax_rate_id
+            );
+            Err(ShoppingCartError::storage(message, error))
+        }
+    }
+}
+
+/// Builds an aggregation expression which evaluates to the new version if it is newer than `$current_version`.
+///
+/// * `version` - Version number of the new version.
+/// * `new_version` - BSON representation of the new version.
+fn newer_version_expression(version: u32, new_version: Bson) -> Document {
+    doc! {"$cond": {
+        "if": {"$gt": [version, {"$ifNull": ["$current_version.version", -1]}]},
+        "then": {"$literal": new_version},
+        "else": "$current_version"
+    }}
+}
+
+/// Defines if a MongoDB error is caused by a duplicate key, which means a redelivered event was already projected.
+///
+/// * `error` - MongoDB error of a write.
+fn is_duplicate_key_error(error: &Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(write_error))
+            if write_error.code == DUPLICATE_KEY_ERROR_CODE
+    )
+}
+
+/// Builds the error returned if an event was published to a topic the endpoint does not handle.
+///
+/// * `topic` - Topic of the event.
+fn unhandled_topic(topic: &str) -> ShoppingCartError {
+    ShoppingCartError::Validation {
+        message: format!("Topic: `{}` is not handled by this endpoint.", topic),
+        id: None,
+    }
+}
+
+/// Builds the error returned if event data cannot be projected to MongoDB.
+///
+/// * `error` - Error of the conversion of the event data.
+fn invalid_event_data(error: impl Display) -> ShoppingCartError {
+    ShoppingCartError::Validation {
+        message: format!("Event data could not be converted to BSON: {}", error),
+        id: None,
     }
 }
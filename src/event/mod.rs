@@ -1 +1,4 @@
+pub mod abandoned_shoppingcart_job;
+pub mod dapr_publisher;
 pub mod http_event_service;
+pub mod shoppingcart_change_feed;
@@ -0,0 +1,357 @@
+use std::time::{Duration, Instant};
+
+use bson::{doc, from_bson, to_bson, Document, Uuid};
+use futures::StreamExt;
+use log::{error, info, warn};
+use mongodb::{
+    change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken},
+    error::{Error, ErrorKind},
+    options::{ChangeStreamOptions, UpdateOptions},
+    Collection, Database,
+};
+use serde::Serialize;
+
+use crate::graphql::subscription::ShoppingCartUpdates;
+
+use super::dapr_publisher::DaprPublisher;
+
+/// Name of the collection the change feed persists its resume token in.
+const RESUME_TOKEN_COLLECTION: &str = "change_stream_resume_tokens";
+
+/// UUID of the resume token document of the shopping cart change feed.
+const RESUME_TOKEN_ID: &str = "users";
+
+/// Delay before the first retry of a failed operation, doubled for every further retry.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum delay between retries of a failed operation.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Server error codes signaling that a change stream cannot be resumed from its resume token.
+///
+/// `InvalidResumeToken` (260), `ChangeStreamFatalError` (280) and `ChangeStreamHistoryLost` (286).
+const UNRESUMABLE_ERROR_CODES: [i32; 3] = [260, 280, 286];
+
+/// Maximum amount of changes handled before the resume token is persisted.
+const RESUME_TOKEN_BATCH_SIZE: usize = 100;
+
+/// Maximum duration a handled change waits for its resume token to be persisted.
+const RESUME_TOKEN_PERSIST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Fields of users whose changes do not change the shopping cart as seen by clients.
+const INTERNAL_FIELDS: [&str; 1] = ["shoppingcart.abandonment_published_at"];
+
+/// Kind of change of a shopping cart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShoppingCartChangeKind {
+    /// Shopping cart was created together with its user.
+    Created,
+    /// Shopping cart was updated.
+    Updated,
+    /// Shopping cart was deleted together with its user.
+    Deleted,
+}
+
+impl ShoppingCartChangeKind {
+    /// Topic changes of this kind are published to.
+    pub fn topic(&self) -> &'static str {
+        match self {
+            ShoppingCartChangeKind::Created => "shoppingcart/shoppingcart/created",
+            ShoppingCartChangeKind::Updated => "shoppingcart/shoppingcart/updated",
+            ShoppingCartChangeKind::Deleted => "shoppingcart/shoppingcart/deleted",
+        }
+    }
+}
+
+/// Event data of a shopping cart change.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShoppingCartChangeEventData {
+    /// UUID of user owning the changed shopping cart.
+    pub user_id: Uuid,
+}
+
+/// Change of the shopping cart of a user, derived from the MongoDB change stream of the `users` collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShoppingCartChange {
+    /// UUID of user owning the changed shopping cart.
+    pub user_id: Uuid,
+    /// Kind of change.
+    pub kind: ShoppingCartChangeKind,
+}
+
+impl ShoppingCartChange {
+    /// Derives a shopping cart change from a change stream event of the `users` collection.
+    ///
+    /// Returns `None` for events which do not change a shopping cart as seen by clients.
+    ///
+    /// * `event` - Change stream event of the `users` collection.
+    fn from_event(event: &ChangeStreamEvent<Document>) -> Option<Self> {
+        let kind = match event.operation_type {
+            OperationType::Insert => ShoppingCartChangeKind::Created,
+            OperationType::Update => {
+                let only_internal_fields_changed =
+                    event
+                        .update_description
+                        .as_ref()
+                        .is_some_and(|update_description| {
+                            update_description
+                                .updated_fields
+                                .keys()
+                                .chain(update_description.removed_fields.iter())
+                                .all(|field| INTERNAL_FIELDS.contains(&field.as_str()))
+                        });
+                if only_internal_fields_changed {
+                    return None;
+                }
+                ShoppingCartChangeKind::Updated
+            }
+            OperationType::Replace => ShoppingCartChangeKind::Updated,
+            OperationType::Delete => ShoppingCartChangeKind::Deleted,
+            _ => return None,
+        };
+        let user_id = event
+            .document_key
+            .as_ref()
+            .and_then(|document_key| document_key.get("_id"))
+            .and_then(|id| from_bson::<Uuid>(id.clone()).ok())?;
+        Some(Self { user_id, kind })
+    }
+}
+
+/// Watches the `users` collection and handles all shopping cart changes, regardless of whether they originate from GraphQL or events.
+///
+/// Every change is broadcast to subscriptions once and published to the topic of its kind, retrying the publication until it succeeds.
+/// Persists the resume token in batches, so changes are handled at least once across restarts.
+/// Reopens the change stream after failures with backoff and runs until the service stops.
+/// The resume token is only discarded if the change stream cannot be resumed from it, which loses the changes since it.
+///
+/// Change streams require MongoDB to run as a replica set.
+///
+/// * `db_client` - MongoDB database client.
+/// * `shoppingcart_updates` - Broadcast the shopping cart changes are sent to.
+/// * `publisher` - Publisher for the Dapr sidecar the shopping cart changes are published with.
+pub async fn watch_shoppingcart_changes(
+    db_client: Database,
+    shoppingcart_updates: ShoppingCartUpdates,
+    publisher: DaprPublisher,
+) {
+    let user_collection: Collection<Document> = db_client.collection::<Document>("users");
+    let resume_token_collection: Collection<Document> =
+        db_client.collection::<Document>(RESUME_TOKEN_COLLECTION);
+    let mut reopen_delay = INITIAL_RETRY_DELAY;
+    loop {
+        let resume_token = load_resume_token(&resume_token_collection).await;
+        let options = ChangeStreamOptions::builder()
+            .resume_after(resume_token.clone())
+            .build();
+        let mut change_stream = match user_collection.watch(None, options).await {
+            Ok(change_stream) => change_stream,
+            Err(error) => {
+                warn!(
+                    "Opening change stream of users failed, MongoDB must run as a replica set: {}",
+                    error
+                );
+                if resume_token.is_some() && is_unresumable(&error) {
+                    discard_resume_token(&resume_token_collection).await;
+                }
+                tokio::time::sleep(reopen_delay).await;
+                reopen_delay = next_retry_delay(reopen_delay);
+                continue;
+            }
+        };
+        info!("Watching change stream of users.");
+        reopen_delay = INITIAL_RETRY_DELAY;
+        let mut unpersisted_resume_token: Option<ResumeToken> = None;
+        let mut unpersisted_changes = 0;
+        let mut persisted_at = Instant::now();
+        loop {
+            let maybe_event =
+                match tokio::time::timeout(RESUME_TOKEN_PERSIST_INTERVAL, change_stream.next())
+                    .await
+                {
+                    Ok(Some(maybe_event)) => Some(maybe_event),
+                    Ok(None) => break,
+                    Err(_) => None,
+                };
+            match maybe_event {
+                Some(Ok(event)) => {
+                    if event.operation_type == OperationType::Invalidate {
+                        discard_resume_token(&resume_token_collection).await;
+                        unpersisted_resume_token = None;
+                        break;
+                    }
+                    if let Some(shoppingcart_change) = ShoppingCartChange::from_event(&event) {
+                        handle_shoppingcart_change(
+                            &shoppingcart_updates,
+                            &publisher,
+                            shoppingcart_change,
+                        )
+                        .await;
+                    }
+                    unpersisted_resume_token = Some(event.id);
+                    unpersisted_changes += 1;
+                }
+                Some(Err(error)) => {
+                    warn!("Change stream of users failed: {}", error);
+                    if is_unresumable(&error) {
+                        discard_resume_token(&resume_token_collection).await;
+                        unpersisted_resume_token = None;
+                    }
+                    break;
+                }
+                None => {}
+            }
+            let batch_complete = unpersisted_changes >= RESUME_TOKEN_BATCH_SIZE
+                || persisted_at.elapsed() >= RESUME_TOKEN_PERSIST_INTERVAL;
+            if batch_complete {
+                if let Some(resume_token) = unpersisted_resume_token.take() {
+                    store_resume_token(&resume_token_collection, &resume_token).await;
+                }
+                unpersisted_changes = 0;
+                persisted_at = Instant::now();
+            }
+        }
+        if let Some(resume_token) = unpersisted_resume_token {
+            store_resume_token(&resume_token_collection, &resume_token).await;
+        }
+        tokio::time::sleep(reopen_delay).await;
+        reopen_delay = next_retry_delay(reopen_delay);
+    }
+}
+
+/// Broadcasts a shopping cart change to subscriptions and publishes it to the topic of its kind.
+///
+/// Subscriptions are notified once, publishing is retried with backoff until it succeeds, so the change feed does not move past unpublished changes.
+///
+/// * `shoppingcart_updates` - Broadcast the shopping cart change is sent to.
+/// * `publisher` - Publisher for the Dapr sidecar.
+/// * `shoppingcart_change` - Change of the shopping cart.
+async fn handle_shoppingcart_change(
+    shoppingcart_updates: &ShoppingCartUpdates,
+    publisher: &DaprPublisher,
+    shoppingcart_change: ShoppingCartChange,
+) {
+    shoppingcart_updates.notify(shoppingcart_change);
+    let event_data = ShoppingCartChangeEventData {
+        user_id: shoppingcart_change.user_id,
+    };
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+    while let Err(error) = publisher
+        .publish(shoppingcart_change.kind.topic(), &event_data)
+        .await
+    {
+        warn!(
+            "Publishing change of shoppingcart of user: `{}` failed, retrying in `{}` seconds: {}",
+            shoppingcart_change.user_id,
+            retry_delay.as_secs(),
+            error
+        );
+        tokio::time::sleep(retry_delay).await;
+        retry_delay = next_retry_delay(retry_delay);
+    }
+}
+
+/// Doubles a retry delay, up to `MAX_RETRY_DELAY`.
+///
+/// * `retry_delay` - Delay before the previous retry.
+fn next_retry_delay(retry_delay: Duration) -> Duration {
+    (retry_delay * 2).min(MAX_RETRY_DELAY)
+}
+
+/// Defines if an error signals that the change stream cannot be resumed from its resume token.
+///
+/// Other errors, e.g. network errors, elections or server selection timeouts, are transient and keep the resume token.
+///
+/// * `error` - Error of opening or iterating the change stream.
+fn is_unresumable(error: &Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Command(command_error) if UNRESUMABLE_ERROR_CODES.contains(&command_error.code)
+    )
+}
+
+/// Loads the persisted resume token of the shopping cart change feed.
+///
+/// * `collection` - MongoDB collection containing the resume token.
+async fn load_resume_token(collection: &Collection<Document>) -> Option<ResumeToken> {
+    let resume_token_doc = collection
+        .find_one(doc! {"_id": RESUME_TOKEN_ID}, None)
+        .await
+        .ok()
+        .flatten()?;
+    let resume_token = resume_token_doc.get("resume_token")?.clone();
+    from_bson(resume_token).ok()
+}
+
+/// Persists the resume token of the shopping cart change feed.
+///
+/// * `collection` - MongoDB collection containing the resume token.
+/// * `resume_token` - Resume token of the last handled change.
+async fn store_resume_token(collection: &Collection<Document>, resume_token: &ResumeToken) {
+    let Ok(resume_token_bson) = to_bson(resume_token) else {
+        return;
+    };
+    let update_options = UpdateOptions::builder().upsert(true).build();
+    if let Err(error) = collection
+        .update_one(
+            doc! {"_id": RESUME_TOKEN_ID},
+            doc! {"$set": {"resume_token": resume_token_bson}},
+            update_options,
+        )
+        .await
+    {
+        warn!("Persisting resume token of users failed: {}", error);
+    }
+}
+
+/// Discards the persisted resume token of the shopping cart change feed, so the change stream restarts at the current time.
+///
+/// Logs the gap at error level, as changes since the resume token are not handled.
+///
+/// * `collection` - MongoDB collection containing the resume token.
+async fn discard_resume_token(collection: &Collection<Document>) {
+    error!("Discarding resume token of users, shoppingcart changes since it are not handled.");
+    if let Err(error) = collection
+        .delete_one(doc! {"_id": RESUME_TOKEN_ID}, None)
+        .await
+    {
+        warn!("Deleting resume token of users failed: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::error::CommandError;
+
+    use super::*;
+
+    /// Builds a server error of a command, as returned by MongoDB.
+    ///
+    /// * `code` - Server error code.
+    fn command_error(code: i32) -> Error {
+        let command_error: CommandError =
+            bson::from_document(doc! {"code": code, "errmsg": "Command failed."}).unwrap();
+        Error::from(ErrorKind::Command(command_error))
+    }
+
+    #[test]
+    fn is_unresumable_keeps_resume_token_on_transient_errors() {
+        assert!(is_unresumable(&command_error(286)));
+        assert!(is_unresumable(&command_error(260)));
+        // NotWritablePrimary after a stepdown and network errors are transient.
+        assert!(!is_unresumable(&command_error(10107)));
+        assert!(!is_unresumable(&Error::from(ErrorKind::from(
+            std::io::ErrorKind::ConnectionReset
+        ))));
+    }
+
+    #[test]
+    fn next_retry_delay_doubles_up_to_maximum() {
+        assert_eq!(
+            next_retry_delay(INITIAL_RETRY_DELAY),
+            Duration::from_secs(2)
+        );
+        assert_eq!(next_retry_delay(Duration::from_secs(40)), MAX_RETRY_DELAY);
+    }
+}
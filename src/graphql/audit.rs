@@ -0,0 +1,153 @@
+use std::future::Future;
+
+use async_graphql::{Context, Result};
+use bson::{DateTime, Uuid};
+use log::error;
+use mongodb::{Collection, Database};
+
+use crate::{
+    authorization::{
+        authorized_user_header, permission::Permission, AuthorizedUserHeader, Impersonation,
+    },
+    error::ShoppingCartError,
+};
+
+use super::model::{
+    audit_log_entry::{AuditLogChange, AuditLogEntry, AuditLogStatus},
+    shoppingcart::ShoppingCart,
+};
+
+/// Result of a mutation on the shopping cart of a user, together with the shopping cart before and after its write.
+pub struct ShoppingCartMutation<T> {
+    /// Value returned by the mutation.
+    pub value: T,
+    /// Shopping cart as read before the write, which is only performed if the shopping cart is unchanged since.
+    pub before: ShoppingCart,
+    /// Shopping cart as returned by the write, equal to `before` if the mutation did not write.
+    pub after: ShoppingCart,
+}
+
+impl<T> ShoppingCartMutation<T> {
+    /// Builds the result of a mutation which did not write the shopping cart.
+    ///
+    /// * `value` - Value returned by the mutation.
+    /// * `shoppingcart` - Shopping cart as read by the mutation.
+    pub fn unchanged(value: T, shoppingcart: ShoppingCart) -> Self {
+        Self {
+            value,
+            before: shoppingcart.clone(),
+            after: shoppingcart,
+        }
+    }
+}
+
+/// Performs a mutation on the shopping cart of a user and records it in the append-only `audit_log` collection if the authorized user does not own the shopping cart.
+///
+/// A pending entry is written before the mutation, so no audited mutation is performed without entry.
+/// A separate entry referencing it records the outcome and the fields changed by the write of the mutation afterwards.
+/// Mutations of invited members are not recorded, as only users permitted to modify data of all users are audited.
+///
+/// * `context` - GraphQL context containing the MongoDB database and the `Authorized-User` header.
+/// * `target_user_id` - UUID of user owning the mutated shopping cart.
+/// * `operation` - Name of the mutation.
+/// * `mutation` - Mutation to perform.
+pub async fn audited<T>(
+    ctx: &Context<'_>,
+    target_user_id: Uuid,
+    operation: &str,
+    mutation: impl Future<Output = Result<ShoppingCartMutation<T>>>,
+) -> Result<T> {
+    let actor = authorized_user_header(ctx)?;
+    if actor.id() == target_user_id || !actor.has_permission(Permission::WriteAll) {
+        return mutation.await.map(|mutation| mutation.value);
+    }
+    let db_client = ctx.data::<Database>()?;
+    let audit_log_collection: Collection<AuditLogEntry> =
+        db_client.collection::<AuditLogEntry>("audit_log");
+    let pending_entry = AuditLogEntry {
+        _id: Uuid::new(),
+        actor_id: actor.id(),
+        actor_roles: actor.role_names(),
+        target_user_id,
+        operation: operation.to_string(),
+        status: AuditLogStatus::Pending,
+        pending_entry_id: None,
+        changes: Vec::new(),
+        query: None,
+        timestamp: DateTime::now(),
+    };
+    if let Err(error) = audit_log_collection.insert_one(&pending_entry, None).await {
+        let message = format!(
+            "Writing the audit log for mutation `{}` on shoppingcart of id: `{}` failed in MongoDB, the mutation was not performed.",
+            operation, target_user_id
+        );
+        return Err(ShoppingCartError::storage(message, error).into());
+    }
+    let result = mutation.await;
+    let (status, changes) = match &result {
+        Ok(mutation) => (
+            AuditLogStatus::Succeeded,
+            AuditLogChange::between(&mutation.before, &mutation.after),
+        ),
+        Err(_) => (AuditLogStatus::Failed, Vec::new()),
+    };
+    let completing_entry = AuditLogEntry {
+        _id: Uuid::new(),
+        status,
+        pending_entry_id: Some(pending_entry._id),
+        changes,
+        timestamp: DateTime::now(),
+        ..pending_entry.clone()
+    };
+    if let Err(error) = audit_log_collection
+        .insert_one(&completing_entry, None)
+        .await
+    {
+        error!(
+            "Audit log entry of UUID: `{}` for mutation `{}` on shoppingcart of id: `{}` remains pending: {}",
+            pending_entry._id, operation, target_user_id, error
+        );
+    }
+    result.map(|mutation| mutation.value)
+}
+
+/// Records a request impersonating another user in the append-only `audit_log` collection.
+///
+/// * `db_client` - MongoDB database client.
+/// * `actor` - `Authorized-User` header of the impersonating user.
+/// * `impersonation` - Impersonation of the request.
+/// * `query` - GraphQL query of the request.
+pub async fn record_impersonation(
+    db_client: &Database,
+    actor: &AuthorizedUserHeader,
+    impersonation: &Impersonation,
+    query: &str,
+) -> Result<()> {
+    let audit_log_collection: Collection<AuditLogEntry> =
+        db_client.collection::<AuditLogEntry>("audit_log");
+    let audit_log_entry = AuditLogEntry {
+        _id: Uuid::new(),
+        actor_id: actor.id(),
+        actor_roles: actor.role_names(),
+        target_user_id: impersonation.user_id(),
+        operation: "impersonation".to_string(),
+        status: AuditLogStatus::Succeeded,
+        pending_entry_id: None,
+        changes: Vec::new(),
+        query: Some(query.to_string()),
+        timestamp: DateTime::now(),
+    };
+    match audit_log_collection
+        .insert_one(&audit_log_entry, None)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            let message = format!(
+                "Writing the audit log for impersonation of user of UUID: `{}` failed in MongoDB.",
+                impersonation.user_id()
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
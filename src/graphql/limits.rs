@@ -0,0 +1,290 @@
+use std::{collections::HashMap, env, str::FromStr};
+
+use async_graphql::Result;
+use bson::Uuid;
+
+use crate::error::ShoppingCartError;
+
+use super::model::{foreign_types::ProductVariant, shoppingcart_item::ShoppingCartItem};
+
+/// Configurable limits for the quantities in a shopping cart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShoppingCartLimits {
+    /// Maximum count of a single shopping cart item, unless overridden by its product variant.
+    pub max_count_per_item: u32,
+    /// Maximum amount of distinct shopping cart items in a shopping cart.
+    pub max_distinct_items: usize,
+    /// Maximum sum of the counts of all shopping cart items in a shopping cart.
+    pub max_total_count: u64,
+    /// Maximum amount of coupons applied to a shopping cart at the same time.
+    pub max_applied_coupons: usize,
+}
+
+impl Default for ShoppingCartLimits {
+    fn default() -> Self {
+        Self {
+            max_count_per_item: 100,
+            max_distinct_items: 100,
+            max_total_count: 1000,
+            max_applied_coupons: 5,
+        }
+    }
+}
+
+/// Quantity rules a shopping cart item of a product variant has to follow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantityRules {
+    /// Minimum count of the shopping cart item, at least `1`, as shopping cart items with a count of zero cannot be checked out.
+    pub min_count: u32,
+    /// Maximum count of the shopping cart item.
+    pub max_count: u32,
+    /// Step the count of the shopping cart item has to be a multiple of.
+    pub count_step: u32,
+}
+
+impl QuantityRules {
+    /// Defines if a count follows the quantity rules.
+    ///
+    /// * `count` - Count of a shopping cart item.
+    pub fn allows(&self, count: u32) -> bool {
+        count >= self.min_count && count <= self.max_count && count.is_multiple_of(self.count_step)
+    }
+
+    /// Finds the valid count closest to a count, preferring the larger count on ties.
+    ///
+    /// Returns `None` if no count follows the quantity rules.
+    ///
+    /// * `count` - Count of a shopping cart item.
+    pub fn nearest_valid_count(&self, count: u32) -> Option<u32> {
+        let step = u64::from(self.count_step);
+        let smallest_valid_count = u64::from(self.min_count).div_ceil(step) * step;
+        let largest_valid_count = u64::from(self.max_count) / step * step;
+        if smallest_valid_count > largest_valid_count {
+            return None;
+        }
+        let clamped_count = u64::from(count).clamp(smallest_valid_count, largest_valid_count);
+        let lower_count = clamped_count / step * step;
+        let upper_count = (lower_count + step).min(largest_valid_count);
+        let nearest_count = match clamped_count - lower_count < upper_count - clamped_count {
+            true => lower_count,
+            false => upper_count,
+        };
+        u32::try_from(nearest_count).ok()
+    }
+}
+
+/// Violation of a shopping cart limit.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LimitViolation {
+    /// UUID of the shopping cart item violating the limit, not set if the limit concerns the whole shopping cart.
+    pub shoppingcart_item_id: Option<Uuid>,
+    /// Human readable description of the violation.
+    pub message: String,
+}
+
+impl ShoppingCartLimits {
+    /// Reads limits from the environment, falling back to the defaults for unset variables.
+    ///
+    /// Uses `$SHOPPINGCART_MAX_COUNT_PER_ITEM`, `$SHOPPINGCART_MAX_DISTINCT_ITEMS`, `$SHOPPINGCART_MAX_TOTAL_COUNT` and `$SHOPPINGCART_MAX_APPLIED_COUPONS`.
+    /// Returns a description of the first variable which is set but cannot be parsed.
+    pub fn from_env() -> Result<Self, String> {
+        let default = Self::default();
+        Ok(Self {
+            max_count_per_item: env_var_or(
+                "SHOPPINGCART_MAX_COUNT_PER_ITEM",
+                default.max_count_per_item,
+            )?,
+            max_distinct_items: env_var_or(
+                "SHOPPINGCART_MAX_DISTINCT_ITEMS",
+                default.max_distinct_items,
+            )?,
+            max_total_count: env_var_or("SHOPPINGCART_MAX_TOTAL_COUNT", default.max_total_count)?,
+            max_applied_coupons: env_var_or(
+                "SHOPPINGCART_MAX_APPLIED_COUPONS",
+                default.max_applied_coupons,
+            )?,
+        })
+    }
+
+    /// Quantity rules of a shopping cart item of a product variant.
+    ///
+    /// The minimum count defaults to `1` and is raised to `1` if the product variant allows a count of zero.
+    ///
+    /// * `maybe_product_variant` - Projected product variant, which might define its own quantity rules.
+    pub fn quantity_rules(&self, maybe_product_variant: Option<&ProductVariant>) -> QuantityRules {
+        QuantityRules {
+            min_count: maybe_product_variant
+                .and_then(|product_variant| product_variant.min_count)
+                .unwrap_or(1)
+                .max(1),
+            max_count: maybe_product_variant
+                .and_then(|product_variant| product_variant.max_count)
+                .unwrap_or(self.max_count_per_item),
+            count_step: maybe_product_variant
+                .and_then(|product_variant| product_variant.count_step)
+                .unwrap_or(1)
+                .max(1),
+        }
+    }
+
+    /// Lists all limit violations of a set of shopping cart items.
+    ///
+    /// * `shoppingcart_items` - Shopping cart items of a shopping cart.
+    /// * `product_variants` - Projected product variants referenced by the shopping cart items, by product variant UUID.
+    pub fn violations<'a>(
+        &self,
+        shoppingcart_items: impl IntoIterator<Item = &'a ShoppingCartItem>,
+        product_variants: &HashMap<Uuid, ProductVariant>,
+    ) -> Vec<LimitViolation> {
+        let shoppingcart_items: Vec<&ShoppingCartItem> = shoppingcart_items.into_iter().collect();
+        let mut violations: Vec<LimitViolation> = shoppingcart_items
+            .iter()
+            .filter_map(|shoppingcart_item| {
+                let quantity_rules = self
+                    .quantity_rules(product_variants.get(&shoppingcart_item.product_variant._id));
+                (!quantity_rules.allows(shoppingcart_item.count)).then(|| LimitViolation {
+                    shoppingcart_item_id: Some(shoppingcart_item._id),
+                    message: count_violation_message(shoppingcart_item, &quantity_rules),
+                })
+            })
+            .collect();
+        if shoppingcart_items.len() > self.max_distinct_items {
+            violations.push(LimitViolation {
+                shoppingcart_item_id: None,
+                message: format!(
+                    "Shopping cart contains more than the maximum of `{}` distinct shopping cart items.",
+                    self.max_distinct_items
+                ),
+            });
+        }
+        let total_count: u64 = shoppingcart_items
+            .iter()
+            .map(|shoppingcart_item| u64::from(shoppingcart_item.count))
+            .sum();
+        if total_count > self.max_total_count {
+            violations.push(LimitViolation {
+                shoppingcart_item_id: None,
+                message: format!(
+                    "Total count of shopping cart items exceeds the maximum of `{}`.",
+                    self.max_total_count
+                ),
+            });
+        }
+        violations
+    }
+
+    /// Validates that a set of shopping cart items does not violate any limit.
+    ///
+    /// Used before adding or modifying shopping cart items.
+    ///
+    /// * `shoppingcart_items` - Shopping cart items of a shopping cart after the modification.
+    /// * `product_variants` - Projected product variants referenced by the shopping cart items, by product variant UUID.
+    pub fn validate<'a>(
+        &self,
+        shoppingcart_items: impl IntoIterator<Item = &'a ShoppingCartItem>,
+        product_variants: &HashMap<Uuid, ProductVariant>,
+    ) -> Result<()> {
+        match self
+            .violations(shoppingcart_items, product_variants)
+            .into_iter()
+            .next()
+        {
+            Some(violation) => Err(ShoppingCartError::Validation {
+                message: violation.message,
+                id: violation.shoppingcart_item_id,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Describes why the count of a shopping cart item violates its quantity rules, including the nearest valid count.
+///
+/// * `shoppingcart_item` - Shopping cart item violating the quantity rules.
+/// * `quantity_rules` - Quantity rules of the shopping cart item.
+fn count_violation_message(
+    shoppingcart_item: &ShoppingCartItem,
+    quantity_rules: &QuantityRules,
+) -> String {
+    let message = format!(
+        "Count `{}` of shopping cart item with product variant of UUID: `{}` is invalid. Count must be between `{}` and `{}` and a multiple of `{}`.",
+        shoppingcart_item.count,
+        shoppingcart_item.product_variant._id,
+        quantity_rules.min_count,
+        quantity_rules.max_count,
+        quantity_rules.count_step
+    );
+    match quantity_rules.nearest_valid_count(shoppingcart_item.count) {
+        Some(nearest_valid_count) => {
+            format!(
+                "{} Nearest valid count is `{}`.",
+                message, nearest_valid_count
+            )
+        }
+        None => format!("{} No valid count exists.", message),
+    }
+}
+
+/// Parses an environment variable, falling back to a default value if it is not set.
+///
+/// Returns a description of the invalid configuration if the environment variable is set but cannot be parsed.
+///
+/// * `key` - Name of the environment variable.
+/// * `default` - Value used if the environment variable is not set.
+pub fn env_var_or<T: FromStr>(key: &str, default: T) -> Result<T, String> {
+    match env::var(key) {
+        Ok(value) => value.parse().map_err(|_| {
+            format!(
+                "`${}` is set to `{}`, which could not be parsed.",
+                key, value
+            )
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_quantity_rules_reject_zero_count() {
+        let quantity_rules = ShoppingCartLimits::default().quantity_rules(None);
+        assert!(!quantity_rules.allows(0));
+        assert!(quantity_rules.allows(1));
+        assert!(quantity_rules.allows(100));
+        assert!(!quantity_rules.allows(101));
+        let mut product_variant = ProductVariant::from(Uuid::new());
+        product_variant.min_count = Some(0);
+        let quantity_rules = ShoppingCartLimits::default().quantity_rules(Some(&product_variant));
+        assert!(!quantity_rules.allows(0));
+    }
+
+    #[test]
+    fn nearest_valid_count_follows_default_quantity_rules() {
+        let quantity_rules = ShoppingCartLimits::default().quantity_rules(None);
+        assert_eq!(quantity_rules.nearest_valid_count(0), Some(1));
+        assert_eq!(quantity_rules.nearest_valid_count(42), Some(42));
+        assert_eq!(quantity_rules.nearest_valid_count(500), Some(100));
+    }
+
+    #[test]
+    fn nearest_valid_count_rounds_to_count_step() {
+        let quantity_rules = QuantityRules {
+            min_count: 1,
+            max_count: 20,
+            count_step: 6,
+        };
+        assert_eq!(quantity_rules.nearest_valid_count(0), Some(6));
+        assert_eq!(quantity_rules.nearest_valid_count(8), Some(6));
+        assert_eq!(quantity_rules.nearest_valid_count(9), Some(12));
+        assert_eq!(quantity_rules.nearest_valid_count(20), Some(18));
+        let unsatisfiable_quantity_rules = QuantityRules {
+            min_count: 7,
+            max_count: 11,
+            count_step: 6,
+        };
+        assert_eq!(unsatisfiable_quantity_rules.nearest_valid_count(9), None);
+    }
+}
@@ -1,4 +1,8 @@
+pub mod audit;
+pub mod limits;
 pub mod model;
 pub mod mutation;
 pub mod mutation_input_structs;
+pub mod pricing;
 pub mod query;
+pub mod subscription;
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use async_graphql::{Enum, SimpleObject};
+use bson::{DateTime, Uuid};
+use serde::{Deserialize, Serialize};
+
+use super::{shoppingcart::ShoppingCart, shoppingcart_member::ShoppingCartMemberPermission};
+
+/// Entry of the audit log, recording a mutation of a user on a shopping cart it does not own or a request impersonating another user.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, SimpleObject)]
+pub struct AuditLogEntry {
+    /// Audit log entry UUID.
+    pub _id: Uuid,
+    /// UUID of the user performing the mutation.
+    pub actor_id: Uuid,
+    /// Roles of the user performing the mutation.
+    pub actor_roles: Vec<String>,
+    /// UUID of the user owning the mutated shopping cart.
+    pub target_user_id: Uuid,
+    /// Name of the mutation, `impersonation` for impersonated requests.
+    pub operation: String,
+    /// Status of the mutation, a `PENDING` entry is written before the mutation is performed and completed by a separate entry afterwards.
+    #[serde(default)]
+    pub status: AuditLogStatus,
+    /// UUID of the `PENDING` entry completed by this entry, not set for pending entries and impersonated requests.
+    #[serde(default)]
+    pub pending_entry_id: Option<Uuid>,
+    /// Fields of the shopping cart changed by the mutation, empty for pending entries, failed mutations and impersonated requests.
+    #[serde(default)]
+    pub changes: Vec<AuditLogChange>,
+    /// GraphQL query of the impersonated request, not set for mutations.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Timestamp when the mutation was performed.
+    pub timestamp: DateTime,
+}
+
+/// Status of an audited mutation.
+///
+/// Entries written before statuses were recorded were only written after successful mutations, so they default to `SUCCEEDED`.
+#[derive(Debug, Enum, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditLogStatus {
+    /// Mutation is being performed, remains without completing entry if the service failed before recording its outcome.
+    Pending,
+    /// Mutation succeeded, impersonated requests are recorded as succeeded once permitted.
+    #[default]
+    Succeeded,
+    /// Mutation failed before or while writing the shopping cart, no changes are recorded.
+    Failed,
+}
+
+/// Change of a single field of a shopping cart.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, SimpleObject)]
+pub struct AuditLogChange {
+    /// Path of the changed field, e.g. `items.<UUID>.count`, `applied_coupon_codes.<code>` or `members.<UUID>.permission`.
+    pub field: String,
+    /// Value before the mutation, not set if the field was added.
+    pub before: Option<String>,
+    /// Value after the mutation, not set if the field was removed.
+    pub after: Option<String>,
+}
+
+impl AuditLogChange {
+    /// Computes the changes of the audited fields between two states of a shopping cart, ordered by field.
+    ///
+    /// Timestamps are not audited, as every mutation updates them.
+    ///
+    /// * `before` - Shopping cart before the mutation.
+    /// * `after` - Shopping cart after the mutation.
+    pub fn between(before: &ShoppingCart, after: &ShoppingCart) -> Vec<Self> {
+        let mut before_fields = audited_fields(before);
+        let after_fields = audited_fields(after);
+        let mut changes: Vec<Self> = after_fields
+            .into_iter()
+            .filter_map(|(field, after_value)| {
+                let before_value = before_fields.remove(&field);
+                (before_value.as_ref() != Some(&after_value)).then_some(Self {
+                    field,
+                    before: before_value,
+                    after: Some(after_value),
+                })
+            })
+            .collect();
+        changes.extend(before_fields.into_iter().map(|(field, before_value)| Self {
+            field,
+            before: Some(before_value),
+            after: None,
+        }));
+        changes.sort_by(|first, second| first.field.cmp(&second.field));
+        changes
+    }
+}
+
+/// Flattens the audited fields of a shopping cart to paths and values.
+///
+/// * `shoppingcart` - Shopping cart to flatten.
+fn audited_fields(shoppingcart: &ShoppingCart) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    for shoppingcart_item in &shoppingcart.internal_shoppingcart_items {
+        let prefix = format!("items.{}", shoppingcart_item._id);
+        fields.insert(
+            format!("{}.count", prefix),
+            shoppingcart_item.count.to_string(),
+        );
+        fields.insert(
+            format!("{}.product_variant_id", prefix),
+            shoppingcart_item.product_variant._id.to_string(),
+        );
+    }
+    for code in &shoppingcart.applied_coupon_codes {
+        fields.insert(
+            format!("applied_coupon_codes.{}", code),
+            "applied".to_string(),
+        );
+    }
+    for member in &shoppingcart.members {
+        let permission = match member.permission {
+            ShoppingCartMemberPermission::View => "VIEW",
+            ShoppingCartMemberPermission::Edit => "EDIT",
+        };
+        fields.insert(
+            format!("members.{}.permission", member.user_id),
+            permission.to_string(),
+        );
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::graphql::model::{
+        foreign_types::ProductVariant, shoppingcart_item::ShoppingCartItem,
+        shoppingcart_member::ShoppingCartMember,
+    };
+
+    use super::*;
+
+    #[test]
+    fn between_lists_added_changed_and_removed_fields() {
+        let shoppingcart_item = ShoppingCartItem {
+            _id: Uuid::new(),
+            count: 1,
+            added_at: DateTime::now(),
+            product_variant: ProductVariant::from(Uuid::new()),
+            added_retail_price: None,
+        };
+        let member_user_id = Uuid::new();
+        let mut before = ShoppingCart::new();
+        before
+            .internal_shoppingcart_items
+            .insert(shoppingcart_item.clone());
+        before.applied_coupon_codes.insert("SUMMER".to_string());
+        let mut after = ShoppingCart::new();
+        after.internal_shoppingcart_items = HashSet::from([ShoppingCartItem {
+            count: 3,
+            ..shoppingcart_item.clone()
+        }]);
+        after.members.push(ShoppingCartMember {
+            user_id: member_user_id,
+            permission: ShoppingCartMemberPermission::Edit,
+        });
+        assert_eq!(
+            AuditLogChange::between(&before, &after),
+            vec![
+                AuditLogChange {
+                    field: "applied_coupon_codes.SUMMER".to_string(),
+                    before: Some("applied".to_string()),
+                    after: None,
+                },
+                AuditLogChange {
+                    field: format!("items.{}.count", shoppingcart_item._id),
+                    before: Some("1".to_string()),
+                    after: Some("3".to_string()),
+                },
+                AuditLogChange {
+                    field: format!("members.{}.permission", member_user_id),
+                    before: None,
+                    after: Some("EDIT".to_string()),
+                },
+            ]
+        );
+        assert!(AuditLogChange::between(&before, &before).is_empty());
+    }
+}
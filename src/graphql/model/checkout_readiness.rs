@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use async_graphql::{Enum, SimpleObject};
+use bson::Uuid;
+
+use crate::graphql::limits::ShoppingCartLimits;
+
+use super::{
+    foreign_types::ProductVariant, shoppingcart::ShoppingCart, shoppingcart_item::ShoppingCartItem,
+};
+
+/// Describes the kinds of problems which prevent a shopping cart from being checked out.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CheckoutProblemKind {
+    /// The shopping cart does not contain any shopping cart items.
+    EmptyShoppingCart,
+    /// The product variant of the shopping cart item is not present in the system.
+    UnknownProductVariant,
+    /// The product variant of the shopping cart item is not publicly visible.
+    InactiveProductVariant,
+    /// The count of the shopping cart item is zero.
+    ZeroCount,
+    /// Less product items are in stock than the count of the shopping cart item.
+    InsufficientStock,
+    /// The retail price of the product variant changed since the shopping cart item was added.
+    PriceChanged,
+    /// A configured shopping cart limit or a quantity rule of a product variant is violated.
+    LimitExceeded,
+}
+
+/// Problem preventing a shopping cart from being checked out.
+#[derive(Debug, PartialEq, Clone, SimpleObject)]
+pub struct CheckoutProblem {
+    /// Kind of the problem.
+    pub kind: CheckoutProblemKind,
+    /// UUID of the shopping cart item causing the problem, not set if the problem concerns the whole shopping cart.
+    pub shoppingcart_item_id: Option<Uuid>,
+    /// Human readable description of the problem.
+    pub message: String,
+}
+
+/// Result of evaluating whether a shopping cart can be checked out.
+#[derive(Debug, PartialEq, Clone, SimpleObject)]
+pub struct CheckoutReadiness {
+    /// Whether the shopping cart can be checked out.
+    pub ready: bool,
+    /// Problems preventing the shopping cart from being checked out.
+    pub problems: Vec<CheckoutProblem>,
+}
+
+impl CheckoutReadiness {
+    /// Evaluates all shopping cart items of a shopping cart against the projected product variants and the shopping cart limits.
+    ///
+    /// * `shoppingcart` - Shopping cart to evaluate.
+    /// * `product_variants` - Projected product variants referenced by the shopping cart, by product variant UUID.
+    /// * `limits` - Shopping cart limits to evaluate against.
+    pub fn evaluate(
+        shoppingcart: &ShoppingCart,
+        product_variants: &HashMap<Uuid, ProductVariant>,
+        limits: &ShoppingCartLimits,
+    ) -> Self {
+        let mut problems = Vec::new();
+        if shoppingcart.internal_shoppingcart_items.is_empty() {
+            problems.push(CheckoutProblem {
+                kind: CheckoutProblemKind::EmptyShoppingCart,
+                shoppingcart_item_id: None,
+                message: "Shopping cart does not contain any shopping cart items.".to_string(),
+            });
+        }
+        for shoppingcart_item in &shoppingcart.internal_shoppingcart_items {
+            problems.extend(evaluate_shoppingcart_item(
+                shoppingcart_item,
+                product_variants.get(&shoppingcart_item.product_variant._id),
+            ));
+        }
+        problems.extend(
+            limits
+                .violations(&shoppingcart.internal_shoppingcart_items, product_variants)
+                .into_iter()
+                .map(|violation| CheckoutProblem {
+                    kind: CheckoutProblemKind::LimitExceeded,
+                    shoppingcart_item_id: violation.shoppingcart_item_id,
+                    message: violation.message,
+                }),
+        );
+        Self {
+            ready: problems.is_empty(),
+            problems,
+        }
+    }
+}
+
+/// Evaluates a single shopping cart item against its projected product variant.
+///
+/// * `shoppingcart_item` - Shopping cart item to evaluate.
+/// * `maybe_product_variant` - Projected product variant of the shopping cart item, if present in the system.
+fn evaluate_shoppingcart_item(
+    shoppingcart_item: &ShoppingCartItem,
+    maybe_product_variant: Option<&ProductVariant>,
+) -> Vec<CheckoutProblem> {
+    let problem = |kind, message| CheckoutProblem {
+        kind,
+        shoppingcart_item_id: Some(shoppingcart_item._id),
+        message,
+    };
+    let product_variant_id = shoppingcart_item.product_variant._id;
+    let mut problems = Vec::new();
+    if shoppingcart_item.count == 0 {
+        problems.push(problem(
+            CheckoutProblemKind::ZeroCount,
+            format!(
+                "ShoppingCartItem of UUID: `{}` has a count of zero.",
+                shoppingcart_item._id
+            ),
+        ));
+    }
+    let Some(product_variant) = maybe_product_variant else {
+        problems.push(problem(
+            CheckoutProblemKind::UnknownProductVariant,
+            format!(
+                "Product variant with the UUID: `{}` is not present in the system.",
+                product_variant_id
+            ),
+        ));
+        return problems;
+    };
+    if product_variant.is_publicly_visible == Some(false) {
+        problems.push(problem(
+            CheckoutProblemKind::InactiveProductVariant,
+            format!(
+                "Product variant with the UUID: `{}` is not publicly visible.",
+                product_variant_id
+            ),
+        ));
+    }
+    if let Some(stock) = product_variant.stock {
+        if stock < u64::from(shoppingcart_item.count) {
+            problems.push(problem(
+                CheckoutProblemKind::InsufficientStock,
+                format!(
+                    "Only `{}` product items of product variant with the UUID: `{}` are in stock, `{}` requested.",
+                    stock, product_variant_id, shoppingcart_item.count
+                ),
+            ));
+        }
+    }
+    if let (Some(added_retail_price), Some(product_variant_version)) = (
+        shoppingcart_item.added_retail_price,
+        product_variant.current_version,
+    ) {
+        if added_retail_price != product_variant_version.retail_price {
+            problems.push(problem(
+                CheckoutProblemKind::PriceChanged,
+                format!(
+                    "Retail price of product variant with the UUID: `{}` changed from `{}` to `{}`.",
+                    product_variant_id, added_retail_price, product_variant_version.retail_price
+                ),
+            ));
+        }
+    }
+    problems
+}
@@ -0,0 +1,31 @@
+use async_graphql::SimpleObject;
+
+use super::{super::audit_log_entry::AuditLogEntry, base_connection::BaseConnection};
+
+/// A connection of audit log entries.
+#[derive(SimpleObject)]
+#[graphql(shareable)]
+pub struct AuditLogEntryConnection {
+    /// The resulting entities.
+    pub nodes: Vec<AuditLogEntry>,
+    /// Whether this connection has a next page.
+    pub has_next_page: bool,
+    /// The total amount of items in this connection.
+    pub total_count: u64,
+    /// Cursor of the last entity, used to retrieve the next page.
+    pub end_cursor: Option<String>,
+}
+
+/// Implementation of conversion from BaseConnection<AuditLogEntry> to AuditLogEntryConnection.
+///
+/// Prevents GraphQL naming conflicts.
+impl From<BaseConnection<AuditLogEntry>> for AuditLogEntryConnection {
+    fn from(value: BaseConnection<AuditLogEntry>) -> Self {
+        Self {
+            nodes: value.nodes,
+            has_next_page: value.has_next_page,
+            total_count: value.total_count,
+            end_cursor: value.end_cursor,
+        }
+    }
+}
@@ -11,16 +11,12 @@ pub struct BaseConnection<T: OutputType> {
     pub has_next_page: bool,
     /// The total amount of items in this connection.
     pub total_count: u64,
+    /// Cursor of the last entity, used to retrieve the next page.
+    pub end_cursor: Option<String>,
 }
 
 pub struct FindResultWrapper<Node>(pub FindResult<Node>);
 
-/// Object that writes total count of items in a query, regardless of pagination.
-#[derive(SimpleObject)]
-pub struct AdditionalFields {
-    total_count: u64,
-}
-
 /// Implementation of conversion from MongoDB pagination to GraphQL connection.
 impl<Node> From<FindResultWrapper<Node>> for BaseConnection<Node>
 where
@@ -31,6 +27,7 @@ where
             nodes: value.0.items,
             has_next_page: value.0.page_info.has_next_page,
             total_count: value.0.total_count,
+            end_cursor: value.0.page_info.next_cursor,
         }
     }
 }
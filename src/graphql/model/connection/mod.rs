@@ -1,2 +1,5 @@
+pub mod audit_log_entry_connection;
 pub mod base_connection;
+pub mod page_info;
 pub mod shoppingcart_item_connection;
+pub mod user_connection;
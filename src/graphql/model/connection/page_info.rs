@@ -0,0 +1,15 @@
+use async_graphql::SimpleObject;
+
+/// Information about the current page of a connection.
+#[derive(SimpleObject, Debug, PartialEq, Clone)]
+#[graphql(shareable)]
+pub struct PageInfo {
+    /// Whether entities before the current page exist.
+    pub has_previous_page: bool,
+    /// Whether entities after the current page exist.
+    pub has_next_page: bool,
+    /// Cursor of the first entity of the current page.
+    pub start_cursor: Option<String>,
+    /// Cursor of the last entity of the current page.
+    pub end_cursor: Option<String>,
+}
@@ -1,6 +1,15 @@
-use async_graphql::SimpleObject;
+use async_graphql::{Result, SimpleObject};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bson::{datetime::DateTime, Uuid};
+use serde::{Deserialize, Serialize};
 
-use super::{super::shoppingcart_item::ShoppingCartItem, base_connection::BaseConnection};
+use crate::error::ShoppingCartError;
+
+use super::{
+    super::{foreign_types::ProductVariant, shoppingcart_item::ShoppingCartItem},
+    base_connection::BaseConnection,
+    page_info::PageInfo,
+};
 
 /// A connection of shopping cart items.
 #[derive(SimpleObject)]
@@ -12,6 +21,56 @@ pub struct ShoppingCartItemConnection {
     pub has_next_page: bool,
     /// The total amount of items in this connection.
     pub total_count: u64,
+    /// The resulting entities with their cursors.
+    pub edges: Vec<ShoppingCartItemEdge>,
+    /// Information about the current page.
+    pub page_info: PageInfo,
+}
+
+/// A shopping cart item with its cursor.
+#[derive(SimpleObject)]
+#[graphql(shareable)]
+pub struct ShoppingCartItemEdge {
+    /// Opaque cursor of the shopping cart item.
+    pub cursor: String,
+    /// The shopping cart item.
+    pub node: ShoppingCartItem,
+}
+
+impl ShoppingCartItemConnection {
+    /// Creates a connection of a page of shopping cart items.
+    ///
+    /// * `nodes` - Shopping cart items of the page.
+    /// * `total_count` - Amount of shopping cart items of all pages.
+    /// * `has_previous_page` - Whether shopping cart items before the page exist.
+    /// * `has_next_page` - Whether shopping cart items after the page exist.
+    pub fn new(
+        nodes: Vec<ShoppingCartItem>,
+        total_count: u64,
+        has_previous_page: bool,
+        has_next_page: bool,
+    ) -> Self {
+        let edges: Vec<ShoppingCartItemEdge> = nodes
+            .iter()
+            .map(|node| ShoppingCartItemEdge {
+                cursor: encode_shoppingcart_item_cursor(node),
+                node: node.clone(),
+            })
+            .collect();
+        let page_info = PageInfo {
+            has_previous_page,
+            has_next_page,
+            start_cursor: edges.first().map(|edge| edge.cursor.clone()),
+            end_cursor: edges.last().map(|edge| edge.cursor.clone()),
+        };
+        Self {
+            nodes,
+            has_next_page,
+            total_count,
+            edges,
+            page_info,
+        }
+    }
 }
 
 /// Implementation of conversion from BaseConnection<ShoppingCart> to ShoppingCartItemConnection.
@@ -19,10 +78,55 @@ pub struct ShoppingCartItemConnection {
 /// Prevents GraphQL naming conflicts.
 impl From<BaseConnection<ShoppingCartItem>> for ShoppingCartItemConnection {
     fn from(value: BaseConnection<ShoppingCartItem>) -> Self {
-        Self {
-            nodes: value.nodes,
-            has_next_page: value.has_next_page,
-            total_count: value.total_count,
-        }
+        Self::new(value.nodes, value.total_count, false, value.has_next_page)
     }
 }
+
+/// Position of a shopping cart item, contains all fields shopping cart items can be ordered by.
+///
+/// Keeps cursors valid if the shopping cart item they point to is removed.
+#[derive(Serialize, Deserialize)]
+struct ShoppingCartItemCursor {
+    _id: Uuid,
+    added_at: DateTime,
+    count: u32,
+    product_variant_id: Uuid,
+}
+
+/// Encodes the position of a shopping cart item as opaque cursor.
+///
+/// * `shoppingcart_item` - Shopping cart item to encode the cursor of.
+pub fn encode_shoppingcart_item_cursor(shoppingcart_item: &ShoppingCartItem) -> String {
+    let cursor = ShoppingCartItemCursor {
+        _id: shoppingcart_item._id,
+        added_at: shoppingcart_item.added_at,
+        count: shoppingcart_item.count,
+        product_variant_id: shoppingcart_item.product_variant._id,
+    };
+    let bytes = bson::to_vec(&cursor).unwrap_or_default();
+    STANDARD.encode(bytes)
+}
+
+/// Decodes an opaque cursor to a shopping cart item only containing the fields shopping cart items can be ordered by.
+///
+/// * `cursor` - Opaque cursor of a shopping cart item.
+pub fn decode_shoppingcart_item_cursor(cursor: &str) -> Result<ShoppingCartItem> {
+    STANDARD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| bson::from_slice::<ShoppingCartItemCursor>(&bytes).ok())
+        .map(|decoded_cursor| ShoppingCartItem {
+            _id: decoded_cursor._id,
+            count: decoded_cursor.count,
+            added_at: decoded_cursor.added_at,
+            product_variant: ProductVariant::from(decoded_cursor.product_variant_id),
+            added_retail_price: None,
+        })
+        .ok_or_else(|| {
+            ShoppingCartError::Validation {
+                message: format!("Cursor: `{}` is invalid.", cursor),
+                id: None,
+            }
+            .into()
+        })
+}
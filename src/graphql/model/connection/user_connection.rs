@@ -0,0 +1,31 @@
+use async_graphql::SimpleObject;
+
+use super::{super::user::User, base_connection::BaseConnection};
+
+/// A connection of users and their shopping carts.
+#[derive(SimpleObject)]
+#[graphql(shareable)]
+pub struct UserConnection {
+    /// The resulting entities.
+    pub nodes: Vec<User>,
+    /// Whether this connection has a next page.
+    pub has_next_page: bool,
+    /// The total amount of items in this connection.
+    pub total_count: u64,
+    /// Cursor of the last entity, used to retrieve the next page.
+    pub end_cursor: Option<String>,
+}
+
+/// Implementation of conversion from BaseConnection<User> to UserConnection.
+///
+/// Prevents GraphQL naming conflicts.
+impl From<BaseConnection<User>> for UserConnection {
+    fn from(value: BaseConnection<User>) -> Self {
+        Self {
+            nodes: value.nodes,
+            has_next_page: value.has_next_page,
+            total_count: value.total_count,
+            end_cursor: value.end_cursor,
+        }
+    }
+}
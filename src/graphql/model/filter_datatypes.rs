@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use async_graphql::InputObject;
+use bson::{doc, DateTime, Document, Uuid};
+
+use super::shoppingcart_item::ShoppingCartItem;
+
+/// Specifies which shoppingcarts are retrieved.
+#[derive(InputObject, Default)]
+pub struct ShoppingCartFilterInput {
+    /// Retrieves only shoppingcarts containing shopping cart items if `true`, only empty shoppingcarts if `false`.
+    pub non_empty: Option<bool>,
+    /// Retrieves only shoppingcarts which were last updated at or after this timestamp.
+    pub updated_since: Option<DateTime>,
+}
+
+/// Implements conversion to a MongoDB query document.
+impl From<ShoppingCartFilterInput> for Document {
+    fn from(value: ShoppingCartFilterInput) -> Self {
+        let mut filter = Document::new();
+        if let Some(non_empty) = value.non_empty {
+            filter.insert(
+                "shoppingcart.internal_shoppingcart_items.0",
+                doc! {"$exists": non_empty},
+            );
+        }
+        if let Some(updated_since) = value.updated_since {
+            filter.insert("shoppingcart.last_updated_at", doc! {"$gte": updated_since});
+        }
+        filter
+    }
+}
+
+/// Builds a MongoDB query document matching abandoned shoppingcarts.
+///
+/// * `inactive_since` - Shoppingcarts last updated before this timestamp are abandoned.
+/// * `min_items` - Minimum amount of shopping cart items an abandoned shoppingcart contains.
+pub fn abandoned_shoppingcarts_filter(inactive_since: DateTime, min_items: usize) -> Document {
+    let mut filter = doc! {"shoppingcart.last_updated_at": {"$lt": inactive_since}};
+    if let Some(last_required_index) = min_items.checked_sub(1) {
+        filter.insert(
+            format!(
+                "shoppingcart.internal_shoppingcart_items.{}",
+                last_required_index
+            ),
+            doc! {"$exists": true},
+        );
+    }
+    filter
+}
+
+/// Specifies an inclusive range of timestamps.
+#[derive(InputObject, Default)]
+pub struct DateTimeRangeInput {
+    /// Lower bound of the range, unbounded if not set.
+    pub from: Option<DateTime>,
+    /// Upper bound of the range, unbounded if not set.
+    pub to: Option<DateTime>,
+}
+
+impl DateTimeRangeInput {
+    /// Defines if a timestamp lies within the range.
+    ///
+    /// * `timestamp` - Timestamp to check.
+    pub fn contains(&self, timestamp: DateTime) -> bool {
+        self.from.map(|from| from <= timestamp).unwrap_or(true)
+            && self.to.map(|to| timestamp <= to).unwrap_or(true)
+    }
+
+    /// Builds a MongoDB query operator document matching timestamps within the range.
+    pub fn to_query_operators(&self) -> Document {
+        let mut operators = Document::new();
+        if let Some(from) = self.from {
+            operators.insert("$gte", from);
+        }
+        if let Some(to) = self.to {
+            operators.insert("$lte", to);
+        }
+        operators
+    }
+}
+
+/// Specifies an inclusive range of counts.
+#[derive(InputObject, Default)]
+pub struct CountRangeInput {
+    /// Lower bound of the range, unbounded if not set.
+    pub min: Option<u32>,
+    /// Upper bound of the range, unbounded if not set.
+    pub max: Option<u32>,
+}
+
+impl CountRangeInput {
+    /// Defines if a count lies within the range.
+    ///
+    /// * `count` - Count to check.
+    pub fn contains(&self, count: u32) -> bool {
+        self.min.map(|min| min <= count).unwrap_or(true)
+            && self.max.map(|max| count <= max).unwrap_or(true)
+    }
+}
+
+/// Specifies which shopping cart items are retrieved.
+#[derive(InputObject, Default)]
+pub struct ShoppingCartItemFilterInput {
+    /// Retrieves only shopping cart items referencing one of these product variants.
+    pub product_variant_ids: Option<HashSet<Uuid>>,
+    /// Retrieves only shopping cart items added within this range.
+    pub added_at: Option<DateTimeRangeInput>,
+    /// Retrieves only shopping cart items with a count within this range.
+    pub count: Option<CountRangeInput>,
+}
+
+impl ShoppingCartItemFilterInput {
+    /// Defines if a shopping cart item matches all conditions of the filter.
+    ///
+    /// * `shoppingcart_item` - Shopping cart item to check.
+    pub fn matches(&self, shoppingcart_item: &ShoppingCartItem) -> bool {
+        let matches_product_variant_ids = self
+            .product_variant_ids
+            .as_ref()
+            .map(|product_variant_ids| {
+                product_variant_ids.contains(&shoppingcart_item.product_variant._id)
+            })
+            .unwrap_or(true);
+        let matches_added_at = self
+            .added_at
+            .as_ref()
+            .map(|added_at| added_at.contains(shoppingcart_item.added_at))
+            .unwrap_or(true);
+        let matches_count = self
+            .count
+            .as_ref()
+            .map(|count| count.contains(shoppingcart_item.count))
+            .unwrap_or(true);
+        matches_product_variant_ids && matches_added_at && matches_count
+    }
+}
@@ -1,5 +1,5 @@
 use async_graphql::SimpleObject;
-use bson::{doc, Bson, Uuid};
+use bson::{doc, Bson, DateTime, Uuid};
 use serde::{Deserialize, Serialize};
 use std::{cmp::Ordering, hash::Hash};
 
@@ -9,6 +9,45 @@ use std::{cmp::Ordering, hash::Hash};
 pub struct ProductVariant {
     /// UUID of the product variant.
     pub _id: Uuid,
+    /// Current version of the product variant, projected from catalog events.
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_version: Option<ProductVariantVersion>,
+    /// Whether the product variant is publicly visible, projected from catalog events.
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_publicly_visible: Option<bool>,
+    /// Amount of product items in stock, projected from inventory events.
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock: Option<u64>,
+    /// Minimum count of a shopping cart item of the product variant.
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_count: Option<u32>,
+    /// Maximum count of a shopping cart item of the product variant, overrides the configured limit.
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<u32>,
+    /// Step the count of a shopping cart item of the product variant has to be a multiple of.
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count_step: Option<u32>,
+}
+
+/// Creates a reference to a product variant without projected data.
+impl From<Uuid> for ProductVariant {
+    fn from(value: Uuid) -> Self {
+        Self {
+            _id: value,
+            current_version: None,
+            is_publicly_visible: None,
+            stock: None,
+            min_count: None,
+            max_count: None,
+            count_step: None,
+        }
+    }
 }
 
 impl PartialOrd for ProductVariant {
@@ -22,3 +61,71 @@ impl From<ProductVariant> for Bson {
         Bson::Document(doc!("_id": value._id))
     }
 }
+
+/// Foreign type of a product variant version.
+#[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Copy, Clone)]
+pub struct ProductVariantVersion {
+    /// UUID of the product variant version.
+    pub _id: Uuid,
+    /// Version number of the product variant version.
+    pub version: u32,
+    /// Retail price of the product variant version, excluding taxes.
+    pub retail_price: u32,
+    /// UUID of the tax rate applied to the product variant version.
+    pub tax_rate_id: Uuid,
+}
+
+/// Foreign type of a tax rate.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
+pub struct TaxRate {
+    /// UUID of the tax rate.
+    pub _id: Uuid,
+    /// Current version of the tax rate, projected from tax events.
+    pub current_version: TaxRateVersion,
+}
+
+/// Foreign type of a tax rate version.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
+pub struct TaxRateVersion {
+    /// UUID of the tax rate version.
+    pub _id: Uuid,
+    /// Version number of the tax rate version.
+    pub version: u32,
+    /// Rate of the tax rate version, e.g. `0.19` for 19%.
+    pub rate: f64,
+}
+
+/// Foreign type of a discount which can be applied to a shopping cart using its coupon code.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Discount {
+    /// UUID of the discount.
+    pub _id: Uuid,
+    /// Coupon code used to apply the discount.
+    pub code: String,
+    /// Relative discount, e.g. `0.1` for 10%.
+    pub discount: f64,
+    /// Timestamp from which the discount is valid.
+    pub valid_from: DateTime,
+    /// Timestamp until which the discount is valid.
+    pub valid_until: DateTime,
+    /// UUID of product variant the discount is restricted to, applies to all product variants if not set.
+    pub product_variant_id: Option<Uuid>,
+}
+
+impl Discount {
+    /// Defines if the discount is valid at a timestamp.
+    ///
+    /// * `timestamp` - Timestamp to check validity at.
+    pub fn is_valid_at(&self, timestamp: DateTime) -> bool {
+        self.valid_from <= timestamp && timestamp <= self.valid_until
+    }
+
+    /// Defines if the discount applies to a product variant.
+    ///
+    /// * `product_variant` - Product variant to check.
+    pub fn applies_to(&self, product_variant: &ProductVariant) -> bool {
+        self.product_variant_id
+            .map(|product_variant_id| product_variant_id == product_variant._id)
+            .unwrap_or(true)
+    }
+}
@@ -1,6 +1,13 @@
+pub mod audit_log_entry;
+pub mod checkout_readiness;
 pub mod connection;
+pub mod filter_datatypes;
 pub mod foreign_types;
 pub mod order_datatypes;
+pub mod product_variant_cart_statistics;
 pub mod shoppingcart;
 pub mod shoppingcart_item;
+pub mod shoppingcart_item_discount;
+pub mod shoppingcart_member;
+pub mod shoppingcart_statistics;
 pub mod user;
@@ -1,20 +1,20 @@
+use std::cmp::Ordering;
+
 use async_graphql::{Enum, InputObject, SimpleObject};
+use bson::{doc, Document};
+
+use super::shoppingcart_item::ShoppingCartItem;
 
 /// GraphQL order direction.
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Default)]
 pub enum OrderDirection {
     /// Ascending order direction.
+    #[default]
     Asc,
     /// Descending order direction.
     Desc,
 }
 
-impl Default for OrderDirection {
-    fn default() -> Self {
-        Self::Asc
-    }
-}
-
 /// Implements conversion to `i32` for MongoDB document sorting.
 impl From<OrderDirection> for i32 {
     fn from(value: OrderDirection) -> Self {
@@ -26,15 +26,19 @@ impl From<OrderDirection> for i32 {
 }
 
 /// Describes the fields that a shoppingcart can be ordered by.
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Default)]
 pub enum ShoppingCartOrderField {
     /// Orders by "id".
+    #[graphql(deprecation = "Shoppingcarts are identified by their user, use `USER_ID`.")]
     Id,
     /// Orders by "user_id".
+    #[default]
     UserId,
     /// Orders by "name".
+    #[graphql(deprecation = "Shoppingcarts have no name, orders by `USER_ID`.")]
     Name,
     /// Orders by "created_at".
+    #[graphql(deprecation = "Creation of shoppingcarts is not recorded, orders by `USER_ID`.")]
     CreatedAt,
     /// Orders by "last_updated_at".
     LastUpdatedAt,
@@ -43,21 +47,15 @@ pub enum ShoppingCartOrderField {
 impl ShoppingCartOrderField {
     pub fn as_str(&self) -> &'static str {
         match self {
-            ShoppingCartOrderField::Id => "_id",
-            ShoppingCartOrderField::UserId => "user_id",
-            ShoppingCartOrderField::Name => "name",
-            ShoppingCartOrderField::CreatedAt => "created_at",
-            ShoppingCartOrderField::LastUpdatedAt => "last_updated_at",
+            ShoppingCartOrderField::Id
+            | ShoppingCartOrderField::UserId
+            | ShoppingCartOrderField::Name
+            | ShoppingCartOrderField::CreatedAt => "_id",
+            ShoppingCartOrderField::LastUpdatedAt => "shoppingcart.last_updated_at",
         }
     }
 }
 
-impl Default for ShoppingCartOrderField {
-    fn default() -> Self {
-        Self::Id
-    }
-}
-
 /// Specifies the order of shoppingcarts.
 #[derive(SimpleObject, InputObject)]
 pub struct ShoppingCartOrderInput {
@@ -76,37 +74,67 @@ impl Default for ShoppingCartOrderInput {
     }
 }
 
-/// Describes the fields that a foreign types can be ordered by.
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
-pub enum CommonOrderField {
+/// Describes the fields that shopping cart items can be ordered by.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ShoppingCartItemOrderField {
     /// Orders by "id".
+    #[default]
     Id,
+    /// Orders by "added_at".
+    AddedAt,
+    /// Orders by "count".
+    Count,
+    /// Orders by "product_variant_id".
+    ProductVariantId,
 }
 
-impl CommonOrderField {
+impl ShoppingCartItemOrderField {
     pub fn as_str(&self) -> &'static str {
         match self {
-            CommonOrderField::Id => "_id",
+            ShoppingCartItemOrderField::Id => "_id",
+            ShoppingCartItemOrderField::AddedAt => "added_at",
+            ShoppingCartItemOrderField::Count => "count",
+            ShoppingCartItemOrderField::ProductVariantId => "product_variant._id",
         }
     }
-}
 
-impl Default for CommonOrderField {
-    fn default() -> Self {
-        Self::Id
+    /// Compares two shopping cart items by this field in ascending order.
+    ///
+    /// * `first_shopping_cart_item` - First shopping cart item to compare.
+    /// * `second_shopping_cart_item` - Second shopping cart item to compare.
+    fn compare(
+        &self,
+        first_shopping_cart_item: &ShoppingCartItem,
+        second_shopping_cart_item: &ShoppingCartItem,
+    ) -> Ordering {
+        match self {
+            ShoppingCartItemOrderField::Id => first_shopping_cart_item
+                ._id
+                .cmp(&second_shopping_cart_item._id),
+            ShoppingCartItemOrderField::AddedAt => first_shopping_cart_item
+                .added_at
+                .cmp(&second_shopping_cart_item.added_at),
+            ShoppingCartItemOrderField::Count => first_shopping_cart_item
+                .count
+                .cmp(&second_shopping_cart_item.count),
+            ShoppingCartItemOrderField::ProductVariantId => first_shopping_cart_item
+                .product_variant
+                ._id
+                .cmp(&second_shopping_cart_item.product_variant._id),
+        }
     }
 }
 
-/// Specifies the order of foreign types.
+/// Specifies the order of shopping cart items.
 #[derive(SimpleObject, InputObject)]
-pub struct CommonOrderInput {
-    /// Order direction of shoppingcarts.
+pub struct ShoppingCartItemOrderInput {
+    /// Order direction of shopping cart items.
     pub direction: Option<OrderDirection>,
-    /// Field that shoppingcarts should be ordered by.
-    pub field: Option<CommonOrderField>,
+    /// Field that shopping cart items should be ordered by.
+    pub field: Option<ShoppingCartItemOrderField>,
 }
 
-impl Default for CommonOrderInput {
+impl Default for ShoppingCartItemOrderInput {
     fn default() -> Self {
         Self {
             direction: Some(Default::default()),
@@ -114,3 +142,90 @@ impl Default for CommonOrderInput {
         }
     }
 }
+
+impl ShoppingCartItemOrderInput {
+    /// Compares two shopping cart items according to the order.
+    ///
+    /// Uses the shopping cart item UUID as tie-breaker, which makes the order total and stable.
+    ///
+    /// * `first_shopping_cart_item` - First shopping cart item to compare.
+    /// * `second_shopping_cart_item` - Second shopping cart item to compare.
+    pub fn compare(
+        &self,
+        first_shopping_cart_item: &ShoppingCartItem,
+        second_shopping_cart_item: &ShoppingCartItem,
+    ) -> Ordering {
+        let ordering = self
+            .field
+            .unwrap_or_default()
+            .compare(first_shopping_cart_item, second_shopping_cart_item)
+            .then_with(|| {
+                ShoppingCartItemOrderField::Id
+                    .compare(first_shopping_cart_item, second_shopping_cart_item)
+            });
+        match self.direction.unwrap_or_default() {
+            OrderDirection::Asc => ordering,
+            OrderDirection::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Builds a MongoDB sort document equivalent to `compare`.
+    ///
+    /// Shopping cart items are sorted in memory by the service, this is provided for aggregations sorting unwound shopping cart items.
+    ///
+    /// * `prefix` - Path of the shopping cart items in the sorted documents, e.g. `shoppingcart.internal_shoppingcart_items` after an `$unwind`.
+    #[allow(dead_code)]
+    pub fn to_sort_document(&self, prefix: &str) -> Document {
+        let direction = i32::from(self.direction.unwrap_or_default());
+        let field_path = |field: ShoppingCartItemOrderField| match prefix.is_empty() {
+            true => field.as_str().to_string(),
+            false => format!("{}.{}", prefix, field.as_str()),
+        };
+        let mut sort_document = doc! {field_path(self.field.unwrap_or_default()): direction};
+        sort_document.insert(field_path(ShoppingCartItemOrderField::Id), direction);
+        sort_document
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{DateTime, Uuid};
+
+    use super::super::foreign_types::ProductVariant;
+    use super::*;
+
+    #[test]
+    fn to_sort_document_matches_compare() {
+        let order = ShoppingCartItemOrderInput {
+            direction: Some(OrderDirection::Desc),
+            field: Some(ShoppingCartItemOrderField::Count),
+        };
+        assert_eq!(
+            order.to_sort_document("shoppingcart.internal_shoppingcart_items"),
+            doc! {
+                "shoppingcart.internal_shoppingcart_items.count": -1,
+                "shoppingcart.internal_shoppingcart_items._id": -1
+            }
+        );
+        assert_eq!(
+            ShoppingCartItemOrderInput::default().to_sort_document(""),
+            doc! {"_id": 1}
+        );
+        let shoppingcart_item = ShoppingCartItem {
+            _id: Uuid::new(),
+            count: 2,
+            added_at: DateTime::now(),
+            product_variant: ProductVariant::from(Uuid::new()),
+            added_retail_price: None,
+        };
+        let other_shoppingcart_item = ShoppingCartItem {
+            _id: Uuid::new(),
+            ..shoppingcart_item.clone()
+        };
+        let id_ordering = shoppingcart_item._id.cmp(&other_shoppingcart_item._id);
+        assert_eq!(
+            order.compare(&shoppingcart_item, &other_shoppingcart_item),
+            id_ordering.reverse()
+        );
+    }
+}
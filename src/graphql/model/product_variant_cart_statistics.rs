@@ -0,0 +1,28 @@
+use async_graphql::SimpleObject;
+use bson::Uuid;
+use serde::Deserialize;
+
+/// Statistics about the shopping carts currently containing a product variant.
+#[derive(Debug, Deserialize, PartialEq, Clone, SimpleObject)]
+pub struct ProductVariantCartStatistics {
+    /// UUID of the product variant.
+    #[serde(rename = "_id")]
+    pub product_variant_id: Uuid,
+    /// Amount of shopping carts containing the product variant.
+    pub shoppingcart_count: u64,
+    /// Sum of the counts of all shopping cart items of the product variant.
+    pub total_count: u64,
+}
+
+impl ProductVariantCartStatistics {
+    /// Statistics of a product variant which is not contained in any shopping cart.
+    ///
+    /// * `product_variant_id` - UUID of the product variant.
+    pub fn empty(product_variant_id: Uuid) -> Self {
+        Self {
+            product_variant_id,
+            shoppingcart_count: 0,
+            total_count: 0,
+        }
+    }
+}
@@ -1,15 +1,23 @@
 use std::{cmp::Ordering, collections::HashSet};
 
-use async_graphql::{ComplexObject, Result, SimpleObject};
+use async_graphql::{ComplexObject, Context, Result, SimpleObject};
 
-use bson::datetime::DateTime;
+use bson::{datetime::DateTime, Uuid};
 
+use mongodb::Database;
 use serde::{Deserialize, Serialize};
 
+use crate::graphql::{pricing::Pricing, query::query_valid_discounts};
+
 use super::{
-    connection::shoppingcart_item_connection::ShoppingCartItemConnection,
-    order_datatypes::{CommonOrderInput, OrderDirection},
+    connection::shoppingcart_item_connection::{
+        decode_shoppingcart_item_cursor, ShoppingCartItemConnection,
+    },
+    filter_datatypes::ShoppingCartItemFilterInput,
+    order_datatypes::ShoppingCartItemOrderInput,
     shoppingcart_item::ShoppingCartItem,
+    shoppingcart_item_discount::ShoppingCartItemDiscount,
+    shoppingcart_member::{ShoppingCartMember, ShoppingCartMemberPermission},
 };
 
 /// The shopping cart of a user.
@@ -21,6 +29,16 @@ pub struct ShoppingCart {
     #[graphql(skip)]
     /// Internal attribute containing all shopping cart items.
     pub internal_shoppingcart_items: HashSet<ShoppingCartItem>,
+    /// Coupon codes applied to the shopping cart.
+    #[serde(default)]
+    pub applied_coupon_codes: HashSet<String>,
+    /// Timestamp when the abandonment of the shopping cart was last published.
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abandonment_published_at: Option<DateTime>,
+    /// Members invited by the owning user to collaborate on the shopping cart.
+    #[serde(default)]
+    pub members: Vec<ShoppingCartMember>,
 }
 
 impl ShoppingCart {
@@ -28,66 +46,179 @@ impl ShoppingCart {
         Self {
             last_updated_at: DateTime::now(),
             internal_shoppingcart_items: HashSet::new(),
+            applied_coupon_codes: HashSet::new(),
+            abandonment_published_at: None,
+            members: Vec::new(),
         }
     }
+
+    /// Retrieves the permission of a member of the shopping cart.
+    ///
+    /// * `user_id` - UUID of the member.
+    pub fn member_permission(&self, user_id: Uuid) -> Option<ShoppingCartMemberPermission> {
+        self.members
+            .iter()
+            .find(|member| member.user_id == user_id)
+            .map(|member| member.permission)
+    }
 }
 
 #[ComplexObject]
 impl ShoppingCart {
     /// Retrieves shoppingcart items in shopping cart.
-    async fn shoppingcart_items(
+    ///
+    /// Supports offset based pagination with `skip` and Relay-style cursor based pagination with `after` and `before`.
+    #[allow(clippy::too_many_arguments)]
+    async fn shoppingcart_items<'a>(
         &self,
+        ctx: &Context<'a>,
         #[graphql(desc = "Describes that the `first` N shoppingcarts should be retrieved.")]
         first: Option<usize>,
         #[graphql(desc = "Describes how many shoppingcarts should be skipped at the beginning.")]
         skip: Option<usize>,
-        #[graphql(desc = "Specifies the order in which shoppingcarts are retrieved.")]
-        order_by: Option<CommonOrderInput>,
+        #[graphql(desc = "Describes that the `last` N shoppingcart items should be retrieved.")]
+        last: Option<usize>,
+        #[graphql(
+            desc = "Cursor of the shoppingcart item after which shoppingcart items are retrieved."
+        )]
+        after: Option<String>,
+        #[graphql(
+            desc = "Cursor of the shoppingcart item before which shoppingcart items are retrieved."
+        )]
+        before: Option<String>,
+        #[graphql(desc = "Specifies the order in which shoppingcart items are retrieved.")]
+        order_by: Option<ShoppingCartItemOrderInput>,
+        #[graphql(desc = "Specifies which shoppingcart items are retrieved.")] filter: Option<
+            ShoppingCartItemFilterInput,
+        >,
     ) -> Result<ShoppingCartItemConnection> {
+        if requests_pricing(ctx) {
+            Pricing::load_cached(ctx, &self.internal_shoppingcart_items).await?;
+        }
+        let definitely_filter = filter.unwrap_or_default();
+        let definitely_order_by = order_by.unwrap_or_default();
         let mut shoppingcart_items: Vec<ShoppingCartItem> = self
             .internal_shoppingcart_items
-            .clone()
-            .into_iter()
+            .iter()
+            .filter(|shoppingcart_item| definitely_filter.matches(shoppingcart_item))
+            .cloned()
             .collect();
-        sort_shoppingcart_items(&mut shoppingcart_items, order_by);
+        sort_shoppingcart_items(&mut shoppingcart_items, &definitely_order_by);
         let total_count = shoppingcart_items.len();
-        let definitely_skip = skip.unwrap_or(0);
-        let definitely_first = first.unwrap_or(usize::MAX);
-        let shoppingcart_items_part: Vec<ShoppingCartItem> = shoppingcart_items
-            .into_iter()
-            .skip(definitely_skip)
-            .take(definitely_first)
-            .collect();
-        let has_next_page = total_count > shoppingcart_items_part.len() + definitely_skip;
-        Ok(ShoppingCartItemConnection {
-            nodes: shoppingcart_items_part,
+        let mut start = match after {
+            Some(cursor) => {
+                let after_shoppingcart_item = decode_shoppingcart_item_cursor(&cursor)?;
+                shoppingcart_items.partition_point(|shoppingcart_item| {
+                    definitely_order_by.compare(shoppingcart_item, &after_shoppingcart_item)
+                        != Ordering::Greater
+                })
+            }
+            None => 0,
+        };
+        let mut end = match before {
+            Some(cursor) => {
+                let before_shoppingcart_item = decode_shoppingcart_item_cursor(&cursor)?;
+                shoppingcart_items.partition_point(|shoppingcart_item| {
+                    definitely_order_by.compare(shoppingcart_item, &before_shoppingcart_item)
+                        == Ordering::Less
+                })
+            }
+            None => total_count,
+        }
+        .max(start);
+        start = start.saturating_add(skip.unwrap_or(0)).min(end);
+        if let Some(definitely_first) = first {
+            end = end.min(start.saturating_add(definitely_first));
+        }
+        if let Some(definitely_last) = last {
+            start = start.max(end.saturating_sub(definitely_last));
+        }
+        let has_previous_page = start > 0;
+        let has_next_page = end < total_count;
+        let shoppingcart_items_part: Vec<ShoppingCartItem> =
+            shoppingcart_items.drain(start..end).collect();
+        Ok(ShoppingCartItemConnection::new(
+            shoppingcart_items_part,
+            total_count as u64,
+            has_previous_page,
             has_next_page,
-            total_count: total_count as u64,
-        })
+        ))
+    }
+
+    /// Subtotal of all shopping cart items based on the current retail prices of their product variants, excluding taxes.
+    async fn subtotal<'a>(&self, ctx: &Context<'a>) -> Result<u64> {
+        let pricing = Pricing::load_cached(ctx, &self.internal_shoppingcart_items).await?;
+        self.internal_shoppingcart_items
+            .iter()
+            .map(|shoppingcart_item| pricing.subtotal(shoppingcart_item))
+            .sum()
+    }
+
+    /// Estimated tax of all shopping cart items based on the current tax rates of their product variants.
+    async fn estimated_tax<'a>(&self, ctx: &Context<'a>) -> Result<u64> {
+        let pricing = Pricing::load_cached(ctx, &self.internal_shoppingcart_items).await?;
+        self.internal_shoppingcart_items
+            .iter()
+            .map(|shoppingcart_item| pricing.estimated_tax(shoppingcart_item))
+            .sum()
+    }
+
+    /// Discounts granted on shopping cart items by the currently valid applied coupons.
+    async fn shoppingcart_item_discounts<'a>(
+        &self,
+        ctx: &Context<'a>,
+    ) -> Result<Vec<ShoppingCartItemDiscount>> {
+        let pricing = Pricing::load_cached(ctx, &self.internal_shoppingcart_items).await?;
+        let db_client = ctx.data::<Database>()?;
+        let discounts = query_valid_discounts(db_client, &self.applied_coupon_codes).await?;
+        self.internal_shoppingcart_items
+            .iter()
+            .map(|shoppingcart_item| {
+                Ok(ShoppingCartItemDiscount {
+                    shoppingcart_item_id: shoppingcart_item._id,
+                    discount: pricing.discount(shoppingcart_item, &discounts)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Total discount granted on the shopping cart by the currently valid applied coupons.
+    async fn discount<'a>(&self, ctx: &Context<'a>) -> Result<u64> {
+        let pricing = Pricing::load_cached(ctx, &self.internal_shoppingcart_items).await?;
+        let db_client = ctx.data::<Database>()?;
+        let discounts = query_valid_discounts(db_client, &self.applied_coupon_codes).await?;
+        self.internal_shoppingcart_items
+            .iter()
+            .map(|shoppingcart_item| pricing.discount(shoppingcart_item, &discounts))
+            .sum()
     }
 }
 
-/// Sorts vector of product variants according to base order.
+/// Sorts shopping cart items according to the shopping cart item order.
 ///
-/// * `shoppingcart_items` - Vector of product variants to sort.
+/// * `shoppingcart_items` - Shopping cart items to sort.
 /// * `order_by` - Specifies order of sorted result.
 fn sort_shoppingcart_items(
-    shoppingcart_items: &mut Vec<ShoppingCartItem>,
-    order_by: Option<CommonOrderInput>,
+    shoppingcart_items: &mut [ShoppingCartItem],
+    order_by: &ShoppingCartItemOrderInput,
 ) {
-    let comparator: fn(&ShoppingCartItem, &ShoppingCartItem) -> bool =
-        match order_by.unwrap_or_default().direction.unwrap_or_default() {
-            OrderDirection::Asc => |first_shopping_cart_item, second_shopping_cart_item| {
-                first_shopping_cart_item < second_shopping_cart_item
-            },
-            OrderDirection::Desc => |first_shopping_cart_item, second_shopping_cart_item| {
-                first_shopping_cart_item > second_shopping_cart_item
-            },
-        };
     shoppingcart_items.sort_by(|first_shopping_cart_item, second_shopping_cart_item| {
-        match comparator(first_shopping_cart_item, second_shopping_cart_item) {
-            true => Ordering::Less,
-            false => Ordering::Greater,
-        }
+        order_by.compare(first_shopping_cart_item, second_shopping_cart_item)
     });
 }
+
+/// Defines if the selection of a shopping cart item connection contains fields requiring pricing information.
+///
+/// * `ctx` - GraphQL context of the shopping cart item connection field.
+fn requests_pricing(ctx: &Context) -> bool {
+    let look_ahead = ctx.look_ahead();
+    [
+        look_ahead.field("nodes"),
+        look_ahead.field("edges").field("node"),
+    ]
+    .iter()
+    .any(|shoppingcart_item| {
+        shoppingcart_item.field("subtotal").exists()
+            || shoppingcart_item.field("estimatedTax").exists()
+    })
+}
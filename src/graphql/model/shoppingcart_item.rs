@@ -1,14 +1,17 @@
 use std::cmp::Ordering;
 
-use async_graphql::SimpleObject;
+use async_graphql::{ComplexObject, Context, Result, SimpleObject};
 use bson::Uuid;
 use bson::{datetime::DateTime, doc, Bson};
 use serde::{Deserialize, Serialize};
 
+use crate::graphql::pricing::Pricing;
+
 use super::foreign_types::ProductVariant;
 
 /// Shopping cart item in a shopping cart of a user.
 #[derive(Debug, Serialize, Deserialize, Eq, Hash, PartialEq, Clone, SimpleObject)]
+#[graphql(complex)]
 pub struct ShoppingCartItem {
     /// Shopping cart item UUID.
     pub _id: Uuid,
@@ -18,6 +21,25 @@ pub struct ShoppingCartItem {
     pub added_at: DateTime,
     /// Product variant of shopping cart item.
     pub product_variant: ProductVariant,
+    /// Retail price of the product variant when the shopping cart item was added.
+    #[graphql(skip)]
+    #[serde(default)]
+    pub added_retail_price: Option<u32>,
+}
+
+#[ComplexObject]
+impl ShoppingCartItem {
+    /// Subtotal of shopping cart item based on the current retail price of its product variant, excluding taxes.
+    async fn subtotal<'a>(&self, ctx: &Context<'a>) -> Result<u64> {
+        let pricing = Pricing::load_cached(ctx, [self]).await?;
+        pricing.subtotal(self)
+    }
+
+    /// Estimated tax of shopping cart item based on the current tax rate of its product variant.
+    async fn estimated_tax<'a>(&self, ctx: &Context<'a>) -> Result<u64> {
+        let pricing = Pricing::load_cached(ctx, [self]).await?;
+        pricing.estimated_tax(self)
+    }
 }
 
 impl From<ShoppingCartItem> for Uuid {
@@ -35,7 +57,7 @@ impl PartialOrd for ShoppingCartItem {
 impl From<ShoppingCartItem> for Bson {
     fn from(value: ShoppingCartItem) -> Self {
         Bson::Document(
-            doc! {"_id": value._id, "count": value.count, "added_at": value.added_at, "product_variant": value.product_variant},
+            doc! {"_id": value._id, "count": value.count, "added_at": value.added_at, "product_variant": value.product_variant, "added_retail_price": value.added_retail_price},
         )
     }
 }
@@ -0,0 +1,11 @@
+use async_graphql::SimpleObject;
+use bson::Uuid;
+
+/// Discount granted on a shopping cart item by the coupons applied to its shopping cart.
+#[derive(Debug, PartialEq, Clone, SimpleObject)]
+pub struct ShoppingCartItemDiscount {
+    /// UUID of the discounted shopping cart item.
+    pub shoppingcart_item_id: Uuid,
+    /// Discount amount granted on the shopping cart item.
+    pub discount: u64,
+}
@@ -0,0 +1,36 @@
+use async_graphql::{Enum, SimpleObject};
+use bson::Uuid;
+use serde::{Deserialize, Serialize};
+
+use crate::authorization::permission::Access;
+
+/// Permission of a member invited to the shopping cart of another user.
+#[derive(Debug, Enum, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ShoppingCartMemberPermission {
+    /// Member can read the shopping cart.
+    View,
+    /// Member can read and modify the shopping cart.
+    Edit,
+}
+
+impl ShoppingCartMemberPermission {
+    /// Defines if the member permission grants a kind of access to the shopping cart.
+    ///
+    /// * `access` - Kind of access to the shopping cart.
+    pub fn grants(&self, access: Access) -> bool {
+        match access {
+            Access::Read => true,
+            Access::Write => *self == ShoppingCartMemberPermission::Edit,
+        }
+    }
+}
+
+/// Member invited to the shopping cart of another user.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, SimpleObject)]
+pub struct ShoppingCartMember {
+    /// UUID of the invited user.
+    pub user_id: Uuid,
+    /// Permission of the invited user on the shopping cart.
+    pub permission: ShoppingCartMemberPermission,
+}
@@ -0,0 +1,162 @@
+use async_graphql::{Result, SimpleObject};
+use bson::{doc, Document};
+use futures::TryStreamExt;
+use mongodb::Collection;
+use serde::Deserialize;
+
+use crate::error::ShoppingCartError;
+
+use super::{
+    filter_datatypes::DateTimeRangeInput,
+    product_variant_cart_statistics::ProductVariantCartStatistics, user::User,
+};
+
+/// Maximum amount of product variants in the top product variant lists.
+pub const MAX_TOP_PRODUCT_VARIANTS: usize = 100;
+
+/// Aggregated statistics over non-empty shopping carts.
+#[derive(Debug, Deserialize, PartialEq, Clone, SimpleObject)]
+pub struct ShoppingCartStatistics {
+    /// Amount of non-empty shopping carts.
+    pub shoppingcart_count: u64,
+    /// Average amount of distinct shopping cart items per shopping cart.
+    pub average_distinct_items: f64,
+    /// Average sum of the counts of the shopping cart items per shopping cart.
+    pub average_total_count: f64,
+    /// Amount of shopping carts per amount of distinct shopping cart items, ascending by amount of distinct shopping cart items.
+    pub items_per_shoppingcart_distribution: Vec<ItemsPerShoppingCartBucket>,
+    /// Product variants with the highest total count over all shopping carts.
+    pub top_product_variants_by_count: Vec<ProductVariantCartStatistics>,
+    /// Product variants contained in the most shopping carts.
+    pub top_product_variants_by_shoppingcart_count: Vec<ProductVariantCartStatistics>,
+}
+
+/// Amount of shopping carts containing a specific amount of distinct shopping cart items.
+#[derive(Debug, Deserialize, PartialEq, Clone, SimpleObject)]
+pub struct ItemsPerShoppingCartBucket {
+    /// Amount of distinct shopping cart items.
+    #[serde(rename = "_id")]
+    pub distinct_items: u64,
+    /// Amount of shopping carts containing this amount of distinct shopping cart items.
+    pub shoppingcart_count: u64,
+}
+
+/// Summary part of the aggregation result.
+#[derive(Debug, Deserialize, Default)]
+struct ShoppingCartStatisticsSummary {
+    shoppingcart_count: u64,
+    average_distinct_items: f64,
+    average_total_count: f64,
+}
+
+/// Raw result of the statistics aggregation, one list per facet.
+#[derive(Debug, Deserialize)]
+struct ShoppingCartStatisticsFacets {
+    summary: Vec<ShoppingCartStatisticsSummary>,
+    items_per_shoppingcart_distribution: Vec<ItemsPerShoppingCartBucket>,
+    top_product_variants_by_count: Vec<ProductVariantCartStatistics>,
+    top_product_variants_by_shoppingcart_count: Vec<ProductVariantCartStatistics>,
+}
+
+impl ShoppingCartStatistics {
+    /// Aggregates statistics over the non-empty shopping carts of users in MongoDB.
+    ///
+    /// * `collection` - MongoDB collection of users.
+    /// * `time_range` - Only shopping carts last updated within this range are considered.
+    /// * `top` - Amount of product variants in the top product variant lists, between `1` and `MAX_TOP_PRODUCT_VARIANTS`.
+    pub async fn aggregate(
+        collection: &Collection<User>,
+        time_range: Option<DateTimeRangeInput>,
+        top: usize,
+    ) -> Result<Self> {
+        if !(1..=MAX_TOP_PRODUCT_VARIANTS).contains(&top) {
+            let message = format!(
+                "Amount of top product variants: `{}` must be between `1` and `{}`.",
+                top, MAX_TOP_PRODUCT_VARIANTS
+            );
+            return Err(ShoppingCartError::Validation { message, id: None }.into());
+        }
+        let mut match_doc = doc! {"shoppingcart.internal_shoppingcart_items.0": {"$exists": true}};
+        if let Some(definitely_time_range) = time_range {
+            let operators = definitely_time_range.to_query_operators();
+            if !operators.is_empty() {
+                match_doc.insert("shoppingcart.last_updated_at", operators);
+            }
+        }
+        let top_limit = i64::try_from(top).unwrap_or(i64::MAX);
+        let pipeline = vec![
+            doc! {"$match": match_doc},
+            doc! {"$facet": {
+                "summary": [
+                    {"$project": {
+                        "distinct_items": {"$size": "$shoppingcart.internal_shoppingcart_items"},
+                        "total_count": {"$sum": "$shoppingcart.internal_shoppingcart_items.count"}
+                    }},
+                    {"$group": {
+                        "_id": null,
+                        "shoppingcart_count": {"$sum": 1},
+                        "average_distinct_items": {"$avg": "$distinct_items"},
+                        "average_total_count": {"$avg": "$total_count"}
+                    }}
+                ],
+                "items_per_shoppingcart_distribution": [
+                    {"$group": {
+                        "_id": {"$size": "$shoppingcart.internal_shoppingcart_items"},
+                        "shoppingcart_count": {"$sum": 1}
+                    }},
+                    {"$sort": {"_id": 1}}
+                ],
+                "top_product_variants_by_count": top_product_variants_pipeline("total_count", top_limit),
+                "top_product_variants_by_shoppingcart_count": top_product_variants_pipeline("shoppingcart_count", top_limit),
+            }},
+        ];
+        let message = "Aggregating shoppingcart statistics failed in MongoDB.";
+        let facets_docs: Vec<Document> = match collection.aggregate(pipeline, None).await {
+            Ok(cursor) => cursor
+                .try_collect()
+                .await
+                .map_err(|error| ShoppingCartError::storage(message, error))?,
+            Err(error) => return Err(ShoppingCartError::storage(message, error).into()),
+        };
+        let facets_doc = facets_docs.into_iter().next().ok_or_else(|| {
+            ShoppingCartError::storage(message, "Aggregation returned no document.")
+        })?;
+        let facets: ShoppingCartStatisticsFacets = bson::from_document(facets_doc)?;
+        let summary = facets.summary.into_iter().next().unwrap_or_default();
+        Ok(Self {
+            shoppingcart_count: summary.shoppingcart_count,
+            average_distinct_items: summary.average_distinct_items,
+            average_total_count: summary.average_total_count,
+            items_per_shoppingcart_distribution: facets.items_per_shoppingcart_distribution,
+            top_product_variants_by_count: facets.top_product_variants_by_count,
+            top_product_variants_by_shoppingcart_count: facets
+                .top_product_variants_by_shoppingcart_count,
+        })
+    }
+}
+
+/// Builds the aggregation stages computing the top product variants by a statistics field.
+///
+/// Uses the product variant UUID as tie-breaker.
+///
+/// * `sort_field` - Field of `ProductVariantCartStatistics` to sort descending by.
+/// * `limit` - Amount of product variants to retrieve.
+fn top_product_variants_pipeline(sort_field: &str, limit: i64) -> Vec<Document> {
+    vec![
+        doc! {"$unwind": "$shoppingcart.internal_shoppingcart_items"},
+        doc! {"$group": {
+            "_id": {
+                "user_id": "$_id",
+                "product_variant_id": "$shoppingcart.internal_shoppingcart_items.product_variant._id"
+            },
+            "count": {"$sum": "$shoppingcart.internal_shoppingcart_items.count"}
+        }},
+        doc! {"$group": {
+            "_id": "$_id.product_variant_id",
+            "shoppingcart_count": {"$sum": 1},
+            "total_count": {"$sum": "$count"}
+        }},
+        doc! {"$sort": {sort_field: -1, "_id": 1}},
+        doc! {"$limit": limit},
+    ]
+}
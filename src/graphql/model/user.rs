@@ -2,6 +2,8 @@ use async_graphql::SimpleObject;
 use bson::Uuid;
 use serde::{Deserialize, Serialize};
 
+use crate::authorization::guard::OwnerOrPermissive;
+
 use super::shoppingcart::ShoppingCart;
 
 /// Type of a user owning shoppingcarts.
@@ -10,5 +12,6 @@ pub struct User {
     /// UUID of the user.
     pub _id: Uuid,
     /// Shopping cart of the user.
+    #[graphql(guard = "OwnerOrPermissive::user(self._id)")]
     pub shoppingcart: ShoppingCart,
 }
@@ -1,26 +1,35 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use async_graphql::{Context, Error, Object, Result};
 use bson::Uuid;
 use futures::TryStreamExt;
 use mongodb::{
-    bson::{doc, DateTime},
+    bson::{doc, to_bson, DateTime, Document},
+    options::{FindOneAndUpdateOptions, ReturnDocument},
     Collection, Database,
 };
 
-use crate::authorization::authorize_user;
+use crate::{
+    authorization::{authorized_user_id, guard::OwnerOrPermissive},
+    error::ShoppingCartError,
+};
 
 use super::{
+    audit::{audited, ShoppingCartMutation},
+    limits::ShoppingCartLimits,
     model::{
-        foreign_types::ProductVariant, shoppingcart::ShoppingCart,
-        shoppingcart_item::ShoppingCartItem, user::User,
+        foreign_types::{Discount, ProductVariant},
+        shoppingcart::ShoppingCart,
+        shoppingcart_item::ShoppingCartItem,
+        shoppingcart_member::ShoppingCartMember,
+        user::User,
     },
     mutation_input_structs::{
-        CreateShoppingCartItemInput, ShoppingCartItemInput, UpdateShoppingCartInput,
-        UpdateShoppingCartItemInput,
+        CreateShoppingCartItemInput, ShoppingCartItemInput, ShoppingCartMemberInput,
+        UpdateMyShoppingCartInput, UpdateShoppingCartInput, UpdateShoppingCartItemInput,
     },
     query::{
-        query_object, query_shoppingcart, query_shoppingcart_item,
+        query_object, query_product_variants, query_shoppingcart,
         query_shoppingcart_item_by_product_variant_id_and_user_id, query_shoppingcart_item_user,
     },
 };
@@ -33,56 +42,73 @@ impl Mutation {
     /// Updates shopping cart items of a specific shopping cart referenced with a UUID.
     ///
     /// Formats UUIDs as hyphenated lowercase strings.
+    #[graphql(guard = "OwnerOrPermissive::user(input.id).write()")]
     async fn update_shoppingcart<'a>(
         &self,
         ctx: &Context<'a>,
         #[graphql(desc = "UpdateShoppingCartInput")] input: UpdateShoppingCartInput,
     ) -> Result<ShoppingCart> {
-        authorize_user(&ctx, Some(input.id))?;
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<User> = db_client.collection::<User>("users");
-        let product_variant_collection: Collection<ProductVariant> =
-            db_client.collection::<ProductVariant>("product_variants");
-        let current_timestamp = DateTime::now();
-        update_shopping_cart_items(
-            &collection,
-            &product_variant_collection,
-            &input,
-            &current_timestamp,
+        audited(
+            ctx,
+            input.id,
+            "updateShoppingcart",
+            update_shoppingcart_of_user(ctx, &input),
         )
-        .await?;
-        let shoppingcart = query_shoppingcart(&collection, input.id).await?;
-        Ok(shoppingcart)
+        .await
+    }
+
+    /// Updates shopping cart items of the shopping cart of the user the request is authorized for.
+    #[graphql(guard = "OwnerOrPermissive::authorized_user().write()")]
+    async fn update_my_shoppingcart<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UpdateMyShoppingCartInput")] input: UpdateMyShoppingCartInput,
+    ) -> Result<ShoppingCart> {
+        let user_id = authorized_user_id(ctx)?;
+        let user_input = UpdateShoppingCartInput {
+            id: user_id,
+            shopping_cart_items: input.shopping_cart_items,
+        };
+        update_shoppingcart_of_user(ctx, &user_input)
+            .await
+            .map(|mutation| mutation.value)
     }
 
     /// Adds shopping cart item to a shopping cart.
     ///
     /// Queries for existing item, otherwise adds new shoppingcart item.
+    #[graphql(guard = "OwnerOrPermissive::user(input.id).write()")]
     async fn create_shoppingcart_item<'a>(
         &self,
         ctx: &Context<'a>,
         #[graphql(desc = "CreateShoppingCartItemInput")] input: CreateShoppingCartItemInput,
     ) -> Result<ShoppingCartItem> {
-        authorize_user(&ctx, Some(input.id))?;
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<User> = db_client.collection::<User>("users");
-        let product_variant_collection: Collection<ProductVariant> =
-            db_client.collection::<ProductVariant>("product_variants");
-        validate_user(&collection, input.id).await?;
-        validate_shopping_cart_item(&product_variant_collection, &input.shopping_cart_item).await?;
-        match query_shoppingcart_item_by_product_variant_id_and_user_id(
-            &collection,
-            input.shopping_cart_item.product_variant_id,
+        audited(
+            ctx,
             input.id,
+            "createShoppingcartItem",
+            create_shoppingcart_item_of_user(ctx, input.id, &input.shopping_cart_item),
         )
         .await
-        {
-            Ok(shoppingcart_item) => Ok(shoppingcart_item),
-            Err(_) => add_shoppingcart_item_to_monogdb(&collection, input).await,
-        }
+    }
+
+    /// Adds shopping cart item to the shopping cart of the user the request is authorized for.
+    ///
+    /// Queries for existing item, otherwise adds new shoppingcart item.
+    #[graphql(guard = "OwnerOrPermissive::authorized_user().write()")]
+    async fn create_my_shoppingcart_item<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "ShoppingCartItemInput")] input: ShoppingCartItemInput,
+    ) -> Result<ShoppingCartItem> {
+        let user_id = authorized_user_id(ctx)?;
+        create_shoppingcart_item_of_user(ctx, user_id, &input)
+            .await
+            .map(|mutation| mutation.value)
     }
 
     /// Updates a single shopping cart item.
+    #[graphql(guard = "OwnerOrPermissive::shoppingcart_item(input.id).write()")]
     async fn update_shoppingcart_item<'a>(
         &self,
         ctx: &Context<'a>,
@@ -91,26 +117,17 @@ impl Mutation {
         let db_client = ctx.data::<Database>()?;
         let collection: Collection<User> = db_client.collection::<User>("users");
         let user = query_shoppingcart_item_user(&collection, input.id).await?;
-        authorize_user(&ctx, Some(user._id))?;
-        if let Err(_) = collection
-            .update_one(
-                doc! {"shoppingcart.internal_shoppingcart_items._id": input.id },
-                doc! {"$set": {"shoppingcart.internal_shoppingcart_items.$.count": input.count}},
-                None,
-            )
-            .await
-        {
-            let message = format!(
-                "Updating count of shoppingcart item of id: `{}` failed in MongoDB.",
-                input.id
-            );
-            return Err(Error::new(message));
-        }
-        let shoppingcart_item = query_shoppingcart_item(&collection, input.id).await?;
-        Ok(shoppingcart_item)
+        audited(
+            ctx,
+            user._id,
+            "updateShoppingcartItem",
+            update_shoppingcart_item_of_user(ctx, user._id, &input),
+        )
+        .await
     }
 
     /// Deletes shoppingcart item of UUID.
+    #[graphql(guard = "OwnerOrPermissive::shoppingcart_item(id).write()")]
     async fn delete_shoppingcart_item<'a>(
         &self,
         ctx: &Context<'a>,
@@ -119,72 +136,708 @@ impl Mutation {
         let db_client = ctx.data::<Database>()?;
         let collection: Collection<User> = db_client.collection::<User>("users");
         let user = query_shoppingcart_item_user(&collection, id).await?;
-        authorize_user(&ctx, Some(user._id))?;
-        if let Err(_) = collection
-            .update_one(
-                doc! {"shoppingcart.internal_shoppingcart_items._id": id },
-                doc! {"$pull": {"shoppingcart.internal_shoppingcart_items": {"_id": id}}},
-                None,
-            )
+        audited(
+            ctx,
+            user._id,
+            "deleteShoppingcartItem",
+            delete_shoppingcart_item_of_user(&collection, user._id, id),
+        )
+        .await
+    }
+
+    /// Applies a coupon to the shopping cart of a user.
+    ///
+    /// Applying an already applied coupon has no effect.
+    /// Fails if the configured maximum amount of applied coupons is reached.
+    #[graphql(guard = "OwnerOrPermissive::user(user_id).write()")]
+    async fn apply_coupon<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of user owning the shopping cart.")] user_id: Uuid,
+        #[graphql(desc = "Code of coupon to apply.")] code: String,
+    ) -> Result<ShoppingCart> {
+        audited(
+            ctx,
+            user_id,
+            "applyCoupon",
+            apply_coupon_to_shoppingcart(ctx, user_id, &code),
+        )
+        .await
+    }
+
+    /// Applies a coupon to the shopping cart of the user the request is authorized for.
+    ///
+    /// Applying an already applied coupon has no effect.
+    /// Fails if the configured maximum amount of applied coupons is reached.
+    #[graphql(guard = "OwnerOrPermissive::authorized_user().write()")]
+    async fn apply_my_coupon<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "Code of coupon to apply.")] code: String,
+    ) -> Result<ShoppingCart> {
+        let user_id = authorized_user_id(ctx)?;
+        apply_coupon_to_shoppingcart(ctx, user_id, &code)
             .await
-        {
+            .map(|mutation| mutation.value)
+    }
+
+    /// Removes a coupon from the shopping cart of a user.
+    #[graphql(guard = "OwnerOrPermissive::user(user_id).write()")]
+    async fn remove_coupon<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of user owning the shopping cart.")] user_id: Uuid,
+        #[graphql(desc = "Code of coupon to remove.")] code: String,
+    ) -> Result<ShoppingCart> {
+        audited(
+            ctx,
+            user_id,
+            "removeCoupon",
+            remove_coupon_from_shoppingcart(ctx, user_id, &code),
+        )
+        .await
+    }
+
+    /// Removes a coupon from the shopping cart of the user the request is authorized for.
+    #[graphql(guard = "OwnerOrPermissive::authorized_user().write()")]
+    async fn remove_my_coupon<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "Code of coupon to remove.")] code: String,
+    ) -> Result<ShoppingCart> {
+        let user_id = authorized_user_id(ctx)?;
+        remove_coupon_from_shoppingcart(ctx, user_id, &code)
+            .await
+            .map(|mutation| mutation.value)
+    }
+
+    /// Invites a user to collaborate on the shopping cart of a user.
+    ///
+    /// Only permitted for the owning user and users permitted to modify data of all users, not for members of the shopping cart.
+    #[graphql(guard = "OwnerOrPermissive::user(input.id).write().excluding_members()")]
+    async fn invite_shoppingcart_member<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "ShoppingCartMemberInput")] input: ShoppingCartMemberInput,
+    ) -> Result<ShoppingCart> {
+        audited(
+            ctx,
+            input.id,
+            "inviteShoppingcartMember",
+            invite_member_to_shoppingcart(ctx, &input),
+        )
+        .await
+    }
+
+    /// Changes the permission of a member of the shopping cart of a user.
+    ///
+    /// Only permitted for the owning user and users permitted to modify data of all users, not for members of the shopping cart.
+    #[graphql(guard = "OwnerOrPermissive::user(input.id).write().excluding_members()")]
+    async fn update_shoppingcart_member<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "ShoppingCartMemberInput")] input: ShoppingCartMemberInput,
+    ) -> Result<ShoppingCart> {
+        audited(
+            ctx,
+            input.id,
+            "updateShoppingcartMember",
+            update_member_of_shoppingcart(ctx, &input),
+        )
+        .await
+    }
+
+    /// Revokes the membership of a user in the shopping cart of a user.
+    ///
+    /// Only permitted for the owning user and users permitted to modify data of all users, not for members of the shopping cart.
+    #[graphql(guard = "OwnerOrPermissive::user(user_id).write().excluding_members()")]
+    async fn revoke_shoppingcart_member<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of user owning the shopping cart.")] user_id: Uuid,
+        #[graphql(desc = "UUID of the member to revoke.")] member_user_id: Uuid,
+    ) -> Result<ShoppingCart> {
+        audited(
+            ctx,
+            user_id,
+            "revokeShoppingcartMember",
+            revoke_member_of_shoppingcart(ctx, user_id, member_user_id),
+        )
+        .await
+    }
+}
+
+/// Updates shopping cart items of the shopping cart of a user, who is already authorized.
+///
+/// * `context` - GraphQL context containing the MongoDB database and shopping cart limits.
+/// * `input` - Update shopping cart input containing the UUID of the user and shopping cart items.
+async fn update_shoppingcart_of_user<'a>(
+    ctx: &Context<'a>,
+    input: &UpdateShoppingCartInput,
+) -> Result<ShoppingCartMutation<ShoppingCart>> {
+    let db_client = ctx.data::<Database>()?;
+    let limits = ctx.data::<ShoppingCartLimits>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    let product_variant_collection: Collection<ProductVariant> =
+        db_client.collection::<ProductVariant>("product_variants");
+    let current_timestamp = DateTime::now();
+    let shoppingcart = query_shoppingcart(&collection, input.id).await?;
+    let updated_shoppingcart = update_shopping_cart_items(
+        &collection,
+        &product_variant_collection,
+        limits,
+        input,
+        &shoppingcart,
+        &current_timestamp,
+    )
+    .await?;
+    Ok(ShoppingCartMutation {
+        value: updated_shoppingcart.clone(),
+        before: shoppingcart,
+        after: updated_shoppingcart,
+    })
+}
+
+/// Adds shopping cart item to the shopping cart of a user, who is already authorized.
+///
+/// Queries for existing item, otherwise adds new shoppingcart item.
+///
+/// * `context` - GraphQL context containing the MongoDB database and shopping cart limits.
+/// * `user_id` - UUID of user owning the shopping cart.
+/// * `shoppingcart_item_input` - Shopping cart item input to add.
+async fn create_shoppingcart_item_of_user<'a>(
+    ctx: &Context<'a>,
+    user_id: Uuid,
+    shoppingcart_item_input: &ShoppingCartItemInput,
+) -> Result<ShoppingCartMutation<ShoppingCartItem>> {
+    let db_client = ctx.data::<Database>()?;
+    let limits = ctx.data::<ShoppingCartLimits>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    let product_variant_collection: Collection<ProductVariant> =
+        db_client.collection::<ProductVariant>("product_variants");
+    validate_user(&collection, user_id).await?;
+    let product_variant =
+        validate_shopping_cart_item(&product_variant_collection, shoppingcart_item_input).await?;
+    match query_shoppingcart_item_by_product_variant_id_and_user_id(
+        &collection,
+        shoppingcart_item_input.product_variant_id,
+        user_id,
+    )
+    .await?
+    {
+        Some(shoppingcart_item) => {
+            let shoppingcart = query_shoppingcart(&collection, user_id).await?;
+            Ok(ShoppingCartMutation::unchanged(
+                shoppingcart_item,
+                shoppingcart,
+            ))
+        }
+        None => {
+            let shoppingcart_item = build_shoppingcart_item(
+                shoppingcart_item_input,
+                Some(&product_variant),
+                DateTime::now(),
+            );
+            let shoppingcart = query_shoppingcart(&collection, user_id).await?;
+            validate_limits(
+                &product_variant_collection,
+                limits,
+                shoppingcart
+                    .internal_shoppingcart_items
+                    .iter()
+                    .chain([&shoppingcart_item]),
+            )
+            .await?;
+            let mut conditions = Document::new();
+            if let Some(last_allowed_index) = limits.max_distinct_items.checked_sub(1) {
+                let last_allowed_field = format!(
+                    "shoppingcart.internal_shoppingcart_items.{}",
+                    last_allowed_index
+                );
+                conditions.insert(last_allowed_field, doc! {"$exists": false});
+            }
+            let updated_shoppingcart = add_shoppingcart_item_to_monogdb(
+                &collection,
+                user_id,
+                &shoppingcart,
+                conditions,
+                &shoppingcart_item,
+            )
+            .await?;
+            Ok(ShoppingCartMutation {
+                value: shoppingcart_item,
+                before: shoppingcart,
+                after: updated_shoppingcart,
+            })
+        }
+    }
+}
+
+/// Updates a single shopping cart item of a user, who is already authorized.
+///
+/// * `context` - GraphQL context containing the MongoDB database and shopping cart limits.
+/// * `user_id` - UUID of user owning the shopping cart item.
+/// * `input` - Update shopping cart item input containing the UUID and new count of the shopping cart item.
+async fn update_shoppingcart_item_of_user<'a>(
+    ctx: &Context<'a>,
+    user_id: Uuid,
+    input: &UpdateShoppingCartItemInput,
+) -> Result<ShoppingCartMutation<ShoppingCartItem>> {
+    let db_client = ctx.data::<Database>()?;
+    let limits = ctx.data::<ShoppingCartLimits>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    let product_variant_collection: Collection<ProductVariant> =
+        db_client.collection::<ProductVariant>("product_variants");
+    let shoppingcart = query_shoppingcart(&collection, user_id).await?;
+    let updated_shoppingcart_items: Vec<ShoppingCartItem> = shoppingcart
+        .internal_shoppingcart_items
+        .iter()
+        .cloned()
+        .map(
+            |shoppingcart_item| match shoppingcart_item._id == input.id {
+                true => ShoppingCartItem {
+                    count: input.count,
+                    ..shoppingcart_item
+                },
+                false => shoppingcart_item,
+            },
+        )
+        .collect();
+    validate_limits(
+        &product_variant_collection,
+        limits,
+        &updated_shoppingcart_items,
+    )
+    .await?;
+    if !shoppingcart
+        .internal_shoppingcart_items
+        .iter()
+        .any(|shoppingcart_item| shoppingcart_item._id == input.id)
+    {
+        return Err(shoppingcart_item_not_found(input.id));
+    }
+    let updated_shoppingcart = match write_unchanged_shoppingcart(
+        &collection,
+        user_id,
+        &shoppingcart,
+        doc! {"shoppingcart.internal_shoppingcart_items._id": input.id},
+        doc! {"$set": {"shoppingcart.internal_shoppingcart_items.$.count": input.count}},
+    )
+    .await
+    {
+        Ok(Some(updated_shoppingcart)) => updated_shoppingcart,
+        Ok(None) => return Err(concurrent_modification(user_id)),
+        Err(error) => {
+            let message = format!(
+                "Updating count of shoppingcart item of id: `{}` failed in MongoDB.",
+                input.id
+            );
+            return Err(ShoppingCartError::storage(message, error).into());
+        }
+    };
+    let shoppingcart_item = updated_shoppingcart
+        .internal_shoppingcart_items
+        .iter()
+        .find(|shoppingcart_item| shoppingcart_item._id == input.id)
+        .cloned()
+        .ok_or_else(|| shoppingcart_item_not_found(input.id))?;
+    Ok(ShoppingCartMutation {
+        value: shoppingcart_item,
+        before: shoppingcart,
+        after: updated_shoppingcart,
+    })
+}
+
+/// Deletes a shopping cart item of a user, who is already authorized.
+///
+/// Deleting a shopping cart item which is not part of the shopping cart has no effect.
+///
+/// * `collection` - MongoDB collection of users.
+/// * `user_id` - UUID of user owning the shopping cart item.
+/// * `id` - UUID of shopping cart item to delete.
+async fn delete_shoppingcart_item_of_user(
+    collection: &Collection<User>,
+    user_id: Uuid,
+    id: Uuid,
+) -> Result<ShoppingCartMutation<bool>> {
+    let shoppingcart = query_shoppingcart(collection, user_id).await?;
+    if !shoppingcart
+        .internal_shoppingcart_items
+        .iter()
+        .any(|shoppingcart_item| shoppingcart_item._id == id)
+    {
+        return Ok(ShoppingCartMutation::unchanged(true, shoppingcart));
+    }
+    match write_unchanged_shoppingcart(
+        collection,
+        user_id,
+        &shoppingcart,
+        Document::new(),
+        doc! {"$pull": {"shoppingcart.internal_shoppingcart_items": {"_id": id}}},
+    )
+    .await
+    {
+        Ok(Some(updated_shoppingcart)) => Ok(ShoppingCartMutation {
+            value: true,
+            before: shoppingcart,
+            after: updated_shoppingcart,
+        }),
+        Ok(None) => Err(concurrent_modification(user_id)),
+        Err(error) => {
             let message = format!(
                 "Deleting shoppingcart item of id: `{}` failed in MongoDB.",
                 id
             );
-            return Err(Error::new(message));
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Applies a coupon to the shopping cart of a user, who is already authorized.
+///
+/// Enforces the maximum amount of applied coupons against the shopping cart as read, which is only written if it is unchanged since, so concurrent applications cannot exceed it.
+///
+/// * `context` - GraphQL context containing the MongoDB database and the shopping cart limits.
+/// * `user_id` - UUID of user owning the shopping cart.
+/// * `code` - Code of coupon to apply.
+async fn apply_coupon_to_shoppingcart<'a>(
+    ctx: &Context<'a>,
+    user_id: Uuid,
+    code: &str,
+) -> Result<ShoppingCartMutation<ShoppingCart>> {
+    let db_client = ctx.data::<Database>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    let discount_collection: Collection<Discount> = db_client.collection::<Discount>("discounts");
+    let shoppingcart = query_shoppingcart(&collection, user_id).await?;
+    validate_coupon_code(&discount_collection, code).await?;
+    let limits = ctx.data::<ShoppingCartLimits>()?;
+    if !shoppingcart.applied_coupon_codes.contains(code)
+        && shoppingcart.applied_coupon_codes.len() >= limits.max_applied_coupons
+    {
+        let message = format!(
+            "Shopping cart already has the maximum of `{}` applied coupons.",
+            limits.max_applied_coupons
+        );
+        return Err(ShoppingCartError::Validation { message, id: None }.into());
+    }
+    match write_unchanged_shoppingcart(
+        &collection,
+        user_id,
+        &shoppingcart,
+        Document::new(),
+        doc! {"$addToSet": {"shoppingcart.applied_coupon_codes": code}},
+    )
+    .await
+    {
+        Ok(Some(updated_shoppingcart)) => Ok(ShoppingCartMutation {
+            value: updated_shoppingcart.clone(),
+            before: shoppingcart,
+            after: updated_shoppingcart,
+        }),
+        Ok(None) => Err(concurrent_modification(user_id)),
+        Err(error) => {
+            let message = format!(
+                "Applying coupon to shoppingcart of id: `{}` failed in MongoDB.",
+                user_id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Removes a coupon from the shopping cart of a user, who is already authorized.
+///
+/// * `context` - GraphQL context containing the MongoDB database.
+/// * `user_id` - UUID of user owning the shopping cart.
+/// * `code` - Code of coupon to remove.
+async fn remove_coupon_from_shoppingcart<'a>(
+    ctx: &Context<'a>,
+    user_id: Uuid,
+    code: &str,
+) -> Result<ShoppingCartMutation<ShoppingCart>> {
+    let db_client = ctx.data::<Database>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    let shoppingcart = query_shoppingcart(&collection, user_id).await?;
+    match write_unchanged_shoppingcart(
+        &collection,
+        user_id,
+        &shoppingcart,
+        Document::new(),
+        doc! {"$pull": {"shoppingcart.applied_coupon_codes": code}},
+    )
+    .await
+    {
+        Ok(Some(updated_shoppingcart)) => Ok(ShoppingCartMutation {
+            value: updated_shoppingcart.clone(),
+            before: shoppingcart,
+            after: updated_shoppingcart,
+        }),
+        Ok(None) => Err(concurrent_modification(user_id)),
+        Err(error) => {
+            let message = format!(
+                "Removing coupon from shoppingcart of id: `{}` failed in MongoDB.",
+                user_id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Invites a user to the shopping cart of a user, who is already authorized.
+///
+/// * `context` - GraphQL context containing the MongoDB database.
+/// * `input` - Shopping cart member input containing the UUIDs of the owning and invited user and the member permission.
+async fn invite_member_to_shoppingcart<'a>(
+    ctx: &Context<'a>,
+    input: &ShoppingCartMemberInput,
+) -> Result<ShoppingCartMutation<ShoppingCart>> {
+    let db_client = ctx.data::<Database>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    if input.id == input.member_user_id {
+        let message = format!(
+            "User of UUID: `{}` owns the shoppingcart and cannot be invited to it.",
+            input.id
+        );
+        return Err(ShoppingCartError::Validation {
+            message,
+            id: Some(input.member_user_id),
+        }
+        .into());
+    }
+    validate_user(&collection, input.member_user_id).await?;
+    let shoppingcart = query_shoppingcart(&collection, input.id).await?;
+    if shoppingcart
+        .member_permission(input.member_user_id)
+        .is_some()
+    {
+        let message = format!(
+            "User of UUID: `{}` is already a member of shoppingcart of id: `{}`.",
+            input.member_user_id, input.id
+        );
+        return Err(ShoppingCartError::Conflict {
+            message,
+            id: Some(input.member_user_id),
+        }
+        .into());
+    }
+    let member = ShoppingCartMember {
+        user_id: input.member_user_id,
+        permission: input.permission,
+    };
+    match write_unchanged_shoppingcart(
+        &collection,
+        input.id,
+        &shoppingcart,
+        Document::new(),
+        doc! {"$push": {"shoppingcart.members": to_bson(&member)?}},
+    )
+    .await
+    {
+        Ok(Some(updated_shoppingcart)) => Ok(ShoppingCartMutation {
+            value: updated_shoppingcart.clone(),
+            before: shoppingcart,
+            after: updated_shoppingcart,
+        }),
+        Ok(None) => Err(concurrent_modification(input.id)),
+        Err(error) => {
+            let message = format!(
+                "Inviting user of UUID: `{}` to shoppingcart of id: `{}` failed in MongoDB.",
+                input.member_user_id, input.id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
         }
-        Ok(true)
     }
 }
 
+/// Changes the permission of a member of the shopping cart of a user, who is already authorized.
+///
+/// * `context` - GraphQL context containing the MongoDB database.
+/// * `input` - Shopping cart member input containing the UUIDs of the owning user and member and the new member permission.
+async fn update_member_of_shoppingcart<'a>(
+    ctx: &Context<'a>,
+    input: &ShoppingCartMemberInput,
+) -> Result<ShoppingCartMutation<ShoppingCart>> {
+    let db_client = ctx.data::<Database>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    let shoppingcart = query_shoppingcart(&collection, input.id).await?;
+    if shoppingcart
+        .member_permission(input.member_user_id)
+        .is_none()
+    {
+        return Err(member_not_found(input.id, input.member_user_id));
+    }
+    match write_unchanged_shoppingcart(
+        &collection,
+        input.id,
+        &shoppingcart,
+        doc! {"shoppingcart.members.user_id": input.member_user_id},
+        doc! {"$set": {"shoppingcart.members.$.permission": to_bson(&input.permission)?}},
+    )
+    .await
+    {
+        Ok(Some(updated_shoppingcart)) => Ok(ShoppingCartMutation {
+            value: updated_shoppingcart.clone(),
+            before: shoppingcart,
+            after: updated_shoppingcart,
+        }),
+        Ok(None) => Err(concurrent_modification(input.id)),
+        Err(error) => {
+            let message = format!(
+                "Updating member of UUID: `{}` of shoppingcart of id: `{}` failed in MongoDB.",
+                input.member_user_id, input.id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Revokes the membership of a user in the shopping cart of a user, who is already authorized.
+///
+/// * `context` - GraphQL context containing the MongoDB database.
+/// * `user_id` - UUID of user owning the shopping cart.
+/// * `member_user_id` - UUID of the member to revoke.
+async fn revoke_member_of_shoppingcart<'a>(
+    ctx: &Context<'a>,
+    user_id: Uuid,
+    member_user_id: Uuid,
+) -> Result<ShoppingCartMutation<ShoppingCart>> {
+    let db_client = ctx.data::<Database>()?;
+    let collection: Collection<User> = db_client.collection::<User>("users");
+    let shoppingcart = query_shoppingcart(&collection, user_id).await?;
+    if shoppingcart.member_permission(member_user_id).is_none() {
+        return Err(member_not_found(user_id, member_user_id));
+    }
+    match write_unchanged_shoppingcart(
+        &collection,
+        user_id,
+        &shoppingcart,
+        Document::new(),
+        doc! {"$pull": {"shoppingcart.members": {"user_id": member_user_id}}},
+    )
+    .await
+    {
+        Ok(Some(updated_shoppingcart)) => Ok(ShoppingCartMutation {
+            value: updated_shoppingcart.clone(),
+            before: shoppingcart,
+            after: updated_shoppingcart,
+        }),
+        Ok(None) => Err(concurrent_modification(user_id)),
+        Err(error) => {
+            let message = format!(
+                "Revoking member of UUID: `{}` of shoppingcart of id: `{}` failed in MongoDB.",
+                member_user_id, user_id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Builds the error returned if a shopping cart item is not part of the shopping cart it was resolved for.
+///
+/// * `id` - UUID of the shopping cart item.
+fn shoppingcart_item_not_found(id: Uuid) -> Error {
+    let message = format!("ShoppingCartItem of UUID: `{}` not found.", id);
+    ShoppingCartError::NotFound {
+        message,
+        id: Some(id),
+    }
+    .into()
+}
+
+/// Builds the error returned if a user is not a member of a shopping cart.
+///
+/// * `user_id` - UUID of user owning the shopping cart.
+/// * `member_user_id` - UUID of the user expected to be a member.
+fn member_not_found(user_id: Uuid, member_user_id: Uuid) -> Error {
+    let message = format!(
+        "User of UUID: `{}` is not a member of shoppingcart of id: `{}`.",
+        member_user_id, user_id
+    );
+    ShoppingCartError::NotFound {
+        message,
+        id: Some(member_user_id),
+    }
+    .into()
+}
+
 /// Updates shopping cart items of a shopping cart.
 ///
+/// Returns the updated shopping cart, the shopping cart as read if the input contains no shopping cart items.
+///
 /// * `collection` - MongoDB collection to update.
 /// * `product_variant_collection` - MongoDB product variant collection used for product variant validation.
+/// * `limits` - Shopping cart limits the updated shopping cart items are validated against.
 /// * `input` - Update withlist input containing shopping cart items.
+/// * `shoppingcart` - Shopping cart as read before the update.
 /// * `current_timestamp` - Timestamp of product variant ids update.
 async fn update_shopping_cart_items(
     collection: &Collection<User>,
     product_variant_collection: &Collection<ProductVariant>,
+    limits: &ShoppingCartLimits,
     input: &UpdateShoppingCartInput,
+    shoppingcart: &ShoppingCart,
     current_timestamp: &DateTime,
-) -> Result<()> {
+) -> Result<ShoppingCart> {
     if let Some(definitely_shopping_cart_items) = &input.shopping_cart_items {
-        validate_shopping_cart_items(&product_variant_collection, definitely_shopping_cart_items)
-            .await?;
-        validate_user(&collection, input.id).await?;
+        let product_variants: HashMap<Uuid, ProductVariant> = validate_shopping_cart_items(
+            product_variant_collection,
+            definitely_shopping_cart_items,
+        )
+        .await?
+        .into_iter()
+        .map(|product_variant| (product_variant._id, product_variant))
+        .collect();
         let normalized_shopping_cart_items: Vec<ShoppingCartItem> = definitely_shopping_cart_items
             .iter()
-            .map(|item_input| ShoppingCartItem {
-                _id: Uuid::new(),
-                count: item_input.count,
-                added_at: *current_timestamp,
-                product_variant: ProductVariant {
-                    _id: item_input.product_variant_id,
-                },
+            .map(|item_input| {
+                build_shoppingcart_item(
+                    item_input,
+                    product_variants.get(&item_input.product_variant_id),
+                    *current_timestamp,
+                )
             })
             .collect();
-        if let Err(_) = collection.update_one(doc!{"_id": input.id }, doc!{"$set": {"shoppingcart.internal_shoppingcart_items": normalized_shopping_cart_items, "shoppingcart.last_updated_at": current_timestamp}}, None).await {
-            let message = format!("Updating product_variant_ids of shoppingcart of id: `{}` failed in MongoDB.", input.id);
-            return Err(Error::new(message))
-        }
+        limits.validate(&normalized_shopping_cart_items, &product_variants)?;
+        let update = doc! {"$set": {
+            "shoppingcart.internal_shoppingcart_items": normalized_shopping_cart_items,
+            "shoppingcart.last_updated_at": current_timestamp
+        }};
+        return match write_unchanged_shoppingcart(
+            collection,
+            input.id,
+            shoppingcart,
+            Document::new(),
+            update,
+        )
+        .await
+        {
+            Ok(Some(updated_shoppingcart)) => Ok(updated_shoppingcart),
+            Ok(None) => Err(concurrent_modification(input.id)),
+            Err(error) => {
+                let message = format!(
+                    "Updating product_variant_ids of shoppingcart of id: `{}` failed in MongoDB.",
+                    input.id
+                );
+                Err(ShoppingCartError::storage(message, error).into())
+            }
+        };
     }
-    Ok(())
+    Ok(shoppingcart.clone())
 }
 
 /// Checks if product variants in shopping cart item inputs are in the system (MongoDB database populated with events).
 ///
 /// Used before adding or modifying shoppingcart items.
+/// Returns the validated product variants.
 ///
 /// * `collection` - MongoDB collection to validate against.
 /// * `shoppingcart_items` - Shopping cart item inputs to validate.
 async fn validate_shopping_cart_items(
     collection: &Collection<ProductVariant>,
     shoppingcart_items: &HashSet<ShoppingCartItemInput>,
-) -> Result<()> {
+) -> Result<Vec<ProductVariant>> {
     let product_variant_ids_vec: Vec<Uuid> = shoppingcart_items
-        .into_iter()
+        .iter()
         .map(|item| item.product_variant_id)
         .collect();
     match collection
@@ -192,58 +845,158 @@ async fn validate_shopping_cart_items(
         .await
     {
         Ok(cursor) => {
-            let product_variants: Vec<ProductVariant> = cursor.try_collect().await?;
-            product_variant_ids_vec.iter().fold(Ok(()), |_, id| {
-                match product_variants.contains(&ProductVariant { _id: *id }) {
+            let product_variants: Vec<ProductVariant> =
+                cursor.try_collect().await.map_err(|error| {
+                    ShoppingCartError::storage(
+                        "Retrieving product variants failed in MongoDB.",
+                        error,
+                    )
+                })?;
+            product_variant_ids_vec.iter().try_for_each(|id| {
+                match product_variants
+                    .iter()
+                    .any(|product_variant| product_variant._id == *id)
+                {
                     true => Ok(()),
                     false => {
                         let message = format!(
                             "Product variant with the UUID: `{}` is not present in the system.",
                             id
                         );
-                        Err(Error::new(message))
+                        Err(ShoppingCartError::Validation {
+                            message,
+                            id: Some(*id),
+                        })
                     }
                 }
-            })
+            })?;
+            Ok(product_variants)
         }
-        Err(_) => Err(Error::new(
-            "Product variants with the specified UUIDs are not present in the system.",
-        )),
+        Err(error) => Err(ShoppingCartError::storage(
+            "Retrieving product variants failed in MongoDB.",
+            error,
+        )
+        .into()),
+    }
+}
+
+/// Builds a new shopping cart item from a shopping cart item input.
+///
+/// * `shoppingcart_item_input` - Shopping cart item input to build the shopping cart item from.
+/// * `maybe_product_variant` - Projected product variant of the shopping cart item, used to record its current retail price.
+/// * `current_timestamp` - Timestamp when the shopping cart item is added.
+fn build_shoppingcart_item(
+    shoppingcart_item_input: &ShoppingCartItemInput,
+    maybe_product_variant: Option<&ProductVariant>,
+    current_timestamp: DateTime,
+) -> ShoppingCartItem {
+    ShoppingCartItem {
+        _id: Uuid::new(),
+        count: shoppingcart_item_input.count,
+        added_at: current_timestamp,
+        product_variant: ProductVariant::from(shoppingcart_item_input.product_variant_id),
+        added_retail_price: maybe_product_variant
+            .and_then(|product_variant| product_variant.current_version)
+            .map(|product_variant_version| product_variant_version.retail_price),
     }
 }
 
 /// Adds shopping cart item to MongoDB collection.
 ///
-/// * `collection` - MongoDB collection to add the shopping cart item to.
-/// * `input` - Create shopping cart item input containing shopping cart item.
+/// Returns the shopping cart after adding the shopping cart item.
+///
+/// * `id` - UUID of user owning the shopping cart.
+/// * `shoppingcart` - Shopping cart as read before adding the shopping cart item.
+/// * `conditions` - Further conditions the shopping cart has to fulfill.
+/// * `shoppingcart_item` - Shopping cart item to add.
 async fn add_shoppingcart_item_to_monogdb(
     collection: &Collection<User>,
-    input: CreateShoppingCartItemInput,
-) -> Result<ShoppingCartItem> {
-    let current_timestamp = DateTime::now();
-    let shoppingcart_item = ShoppingCartItem {
-        _id: Uuid::new(),
-        count: input.shopping_cart_item.count,
-        added_at: current_timestamp,
-        product_variant: ProductVariant {
-            _id: input.shopping_cart_item.product_variant_id,
-        },
-    };
-    if let Err(_) = collection
-        .update_one(
-            doc! {"_id": input.id },
-            doc! {"$push": {"shoppingcart.internal_shoppingcart_items": &shoppingcart_item}},
-            None,
-        )
-        .await
+    id: Uuid,
+    shoppingcart: &ShoppingCart,
+    conditions: Document,
+    shoppingcart_item: &ShoppingCartItem,
+) -> Result<ShoppingCart> {
+    match write_unchanged_shoppingcart(
+        collection,
+        id,
+        shoppingcart,
+        conditions,
+        doc! {"$push": {"shoppingcart.internal_shoppingcart_items": shoppingcart_item}},
+    )
+    .await
     {
-        let message = format!(
-            "Add shoppingcart item of id: `{}` failed in MongoDB.",
-            shoppingcart_item._id
-        );
-        return Err(Error::new(message));
+        Ok(Some(updated_shoppingcart)) => Ok(updated_shoppingcart),
+        Ok(None) => Err(concurrent_modification(id)),
+        Err(error) => {
+            let message = format!(
+                "Add shoppingcart item of id: `{}` failed in MongoDB.",
+                shoppingcart_item._id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
     }
-    Ok(shoppingcart_item)
+}
+
+/// Writes an update to the shopping cart of a user only if it was not modified since it was read.
+///
+/// Sets `shoppingcart.last_updated_at` in addition, so concurrent writes of shopping carts read before do not match.
+/// Returns the shopping cart as written, so the changes of the update are exactly the difference to the shopping cart as read.
+/// Returns `None` if the shopping cart was modified concurrently or does not fulfill the conditions.
+///
+/// * `collection` - MongoDB collection of users.
+/// * `user_id` - UUID of user owning the shopping cart.
+/// * `shoppingcart` - Shopping cart as read before the update.
+/// * `conditions` - Further conditions the shopping cart has to fulfill, e.g. required by positional updates.
+/// * `update` - Update of the shopping cart.
+async fn write_unchanged_shoppingcart(
+    collection: &Collection<User>,
+    user_id: Uuid,
+    shoppingcart: &ShoppingCart,
+    conditions: Document,
+    mut update: Document,
+) -> mongodb::error::Result<Option<ShoppingCart>> {
+    let mut filter = unchanged_shoppingcart_filter(user_id, shoppingcart);
+    filter.extend(conditions);
+    let mut set = update.get_document("$set").cloned().unwrap_or_default();
+    if !set.contains_key("shoppingcart.last_updated_at") {
+        set.insert("shoppingcart.last_updated_at", DateTime::now());
+    }
+    update.insert("$set", set);
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+    let maybe_user = collection
+        .find_one_and_update(filter, update, options)
+        .await?;
+    Ok(maybe_user.map(|user| user.shoppingcart))
+}
+
+/// Builds a filter matching the shopping cart of a user only if it was not modified since it was read.
+///
+/// Used for optimistic concurrency, so limits validated against the read shopping cart still hold when writing.
+///
+/// * `user_id` - UUID of user owning the shopping cart.
+/// * `shoppingcart` - Shopping cart as read before the modification.
+fn unchanged_shoppingcart_filter(user_id: Uuid, shoppingcart: &ShoppingCart) -> Document {
+    doc! {
+        "_id": user_id,
+        "shoppingcart.last_updated_at": shoppingcart.last_updated_at
+    }
+}
+
+/// Builds the error returned if a shopping cart was modified between reading and writing it.
+///
+/// * `user_id` - UUID of user owning the shopping cart.
+fn concurrent_modification(user_id: Uuid) -> Error {
+    let message = format!(
+        "Shoppingcart of id: `{}` was modified concurrently, retry the mutation.",
+        user_id
+    );
+    ShoppingCartError::Conflict {
+        message,
+        id: Some(user_id),
+    }
+    .into()
 }
 
 /// Checks if user is in the system (MongoDB database populated with events).
@@ -251,20 +1004,21 @@ async fn add_shoppingcart_item_to_monogdb(
 /// * `collection` - MongoDB collection to validate against.
 /// * `id` - User UUID to validate.
 async fn validate_user(collection: &Collection<User>, id: Uuid) -> Result<()> {
-    query_object(&collection, id).await.map(|_| ())
+    query_object(collection, id).await.map(|_| ())
 }
 
 /// Checks if product variant in shoppingcart item input is in the system (MongoDB database populated with events).
 ///
 /// Used before adding or modifying shopping cart items.
 /// This is a separate function from `validate_shopping_cart_items`, which is designed for only checking one shopping cart items instead of multiple.
+/// Returns the validated product variant.
 ///
 /// * `collection` - MongoDB collection to validate against.
 /// * `shoppingcart_item_input` - Shopping cart item input to validate.
 async fn validate_shopping_cart_item(
     collection: &Collection<ProductVariant>,
     shoppingcart_item_input: &ShoppingCartItemInput,
-) -> Result<()> {
+) -> Result<ProductVariant> {
     let message = format!(
         "Product variant with the UUID: `{}` is not present in the system.",
         shoppingcart_item_input.product_variant_id
@@ -276,10 +1030,55 @@ async fn validate_shopping_cart_item(
         )
         .await
     {
-        Ok(maybe_product_variant) => match maybe_product_variant {
-            Some(_) => Ok(()),
-            None => Err(Error::new(message)),
+        Ok(maybe_product_variant) => {
+            Ok(maybe_product_variant.ok_or(ShoppingCartError::Validation {
+                message,
+                id: Some(shoppingcart_item_input.product_variant_id),
+            })?)
+        }
+        Err(error) => {
+            let message = format!(
+                "Retrieving product variant with the UUID: `{}` failed in MongoDB.",
+                shoppingcart_item_input.product_variant_id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Checks if a coupon code belongs to a currently valid discount in the system (MongoDB database populated with events).
+///
+/// * `collection` - MongoDB collection to validate against.
+/// * `code` - Coupon code to validate.
+async fn validate_coupon_code(collection: &Collection<Discount>, code: &str) -> Result<()> {
+    let message = format!("Coupon with the code: `{}` is not valid.", code);
+    match collection.find_one(doc! {"code": code }, None).await {
+        Ok(maybe_discount) => match maybe_discount {
+            Some(discount) if discount.is_valid_at(DateTime::now()) => Ok(()),
+            _ => Err(ShoppingCartError::Validation { message, id: None }.into()),
         },
-        Err(_) => Err(Error::new(message)),
+        Err(error) => {
+            let message = format!(
+                "Retrieving coupon with the code: `{}` failed in MongoDB.",
+                code
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
     }
 }
+
+/// Checks if shopping cart items stay within the configured shopping cart limits.
+///
+/// Used before adding or modifying shopping cart items.
+///
+/// * `collection` - MongoDB product variant collection containing per product variant limits.
+/// * `limits` - Shopping cart limits to validate against.
+/// * `shoppingcart_items` - Shopping cart items of the shopping cart after the modification.
+async fn validate_limits<'a>(
+    collection: &Collection<ProductVariant>,
+    limits: &ShoppingCartLimits,
+    shoppingcart_items: impl IntoIterator<Item = &'a ShoppingCartItem> + Clone,
+) -> Result<()> {
+    let product_variants = query_product_variants(collection, shoppingcart_items.clone()).await?;
+    limits.validate(shoppingcart_items, &product_variants)
+}
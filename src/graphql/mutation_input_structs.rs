@@ -2,6 +2,8 @@ use async_graphql::{InputObject, SimpleObject};
 use bson::Uuid;
 use std::collections::HashSet;
 
+use super::model::shoppingcart_member::ShoppingCartMemberPermission;
+
 #[derive(SimpleObject, InputObject)]
 pub struct UpdateShoppingCartInput {
     /// UUID of user owning shopping cart.
@@ -10,6 +12,12 @@ pub struct UpdateShoppingCartInput {
     pub shopping_cart_items: Option<HashSet<ShoppingCartItemInput>>,
 }
 
+#[derive(SimpleObject, InputObject)]
+pub struct UpdateMyShoppingCartInput {
+    /// Shopping cart items of shopping cart of the authorized user to update.
+    pub shopping_cart_items: Option<HashSet<ShoppingCartItemInput>>,
+}
+
 #[derive(SimpleObject, InputObject, Eq, Hash, PartialEq)]
 pub struct ShoppingCartItemInput {
     /// Count of shopping cart items in cart.
@@ -33,3 +41,13 @@ pub struct UpdateShoppingCartItemInput {
     /// Count of shopping cart items in cart.
     pub count: u32,
 }
+
+#[derive(SimpleObject, InputObject)]
+pub struct ShoppingCartMemberInput {
+    /// UUID of user owning the shopping cart.
+    pub id: Uuid,
+    /// UUID of the invited user.
+    pub member_user_id: Uuid,
+    /// Permission of the invited user on the shopping cart.
+    pub permission: ShoppingCartMemberPermission,
+}
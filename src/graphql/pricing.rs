@@ -0,0 +1,203 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use async_graphql::{Context, Result};
+use bson::Uuid;
+use futures::TryStreamExt;
+use mongodb::{bson::doc, Collection, Database};
+
+use crate::error::ShoppingCartError;
+
+use super::{
+    model::{
+        foreign_types::{Discount, ProductVariant, ProductVariantVersion, TaxRate, TaxRateVersion},
+        shoppingcart_item::ShoppingCartItem,
+    },
+    query::query_product_variants,
+};
+
+/// Current product variant versions and tax rates referenced by a set of shopping cart items.
+///
+/// Used to compute subtotals and estimated taxes of shopping cart items without calling the order service.
+pub struct Pricing {
+    /// Current product variant versions by product variant UUID.
+    product_variant_versions: HashMap<Uuid, ProductVariantVersion>,
+    /// Current tax rate versions by tax rate UUID.
+    tax_rate_versions: HashMap<Uuid, TaxRateVersion>,
+}
+
+/// Pricing loaded while executing a single GraphQL request.
+///
+/// Lets the shopping cart and all of its shopping cart items share one load of pricing information from MongoDB.
+#[derive(Default)]
+pub struct PricingCache(Mutex<Vec<Arc<Pricing>>>);
+
+impl Pricing {
+    /// Loads pricing of shopping cart items, reusing pricing already loaded in the current GraphQL request.
+    ///
+    /// Falls back to loading from MongoDB if no `PricingCache` is present in the context.
+    ///
+    /// * `ctx` - GraphQL context containing the MongoDB database and the request-scoped pricing cache.
+    /// * `shoppingcart_items` - Shopping cart items to load pricing information for.
+    pub async fn load_cached<'a>(
+        ctx: &Context<'_>,
+        shoppingcart_items: impl IntoIterator<Item = &'a ShoppingCartItem>,
+    ) -> Result<Arc<Self>> {
+        let shoppingcart_items: Vec<&ShoppingCartItem> = shoppingcart_items.into_iter().collect();
+        let pricing_cache = ctx.data_opt::<PricingCache>();
+        if let Some(pricing_cache) = pricing_cache {
+            let cached_pricings = pricing_cache
+                .0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let cached_pricing = cached_pricings
+                .iter()
+                .find(|pricing| pricing.covers(&shoppingcart_items));
+            if let Some(cached_pricing) = cached_pricing {
+                return Ok(cached_pricing.clone());
+            }
+        }
+        let db_client = ctx.data::<Database>()?;
+        let pricing = Arc::new(Self::load(db_client, shoppingcart_items).await?);
+        if let Some(pricing_cache) = pricing_cache {
+            pricing_cache
+                .0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(pricing.clone());
+        }
+        Ok(pricing)
+    }
+
+    /// Loads product variant versions and tax rates referenced by shopping cart items from MongoDB.
+    ///
+    /// * `db_client` - MongoDB database containing the product variant and tax rate projections.
+    /// * `shoppingcart_items` - Shopping cart items to load pricing information for.
+    pub async fn load<'a>(
+        db_client: &Database,
+        shoppingcart_items: impl IntoIterator<Item = &'a ShoppingCartItem>,
+    ) -> Result<Self> {
+        let product_variant_collection: Collection<ProductVariant> =
+            db_client.collection::<ProductVariant>("product_variants");
+        let tax_rate_collection: Collection<TaxRate> = db_client.collection::<TaxRate>("tax_rates");
+        let product_variants =
+            query_product_variants(&product_variant_collection, shoppingcart_items).await?;
+        let product_variant_versions: HashMap<Uuid, ProductVariantVersion> = product_variants
+            .into_values()
+            .filter_map(|product_variant| {
+                product_variant
+                    .current_version
+                    .map(|current_version| (product_variant._id, current_version))
+            })
+            .collect();
+        let tax_rate_ids: Vec<Uuid> = product_variant_versions
+            .values()
+            .map(|product_variant_version| product_variant_version.tax_rate_id)
+            .collect();
+        let storage_error =
+            |error| ShoppingCartError::storage("Retrieving tax rates failed in MongoDB.", error);
+        let tax_rates: Vec<TaxRate> = tax_rate_collection
+            .find(doc! {"_id": { "$in": &tax_rate_ids } }, None)
+            .await
+            .map_err(storage_error)?
+            .try_collect()
+            .await
+            .map_err(storage_error)?;
+        let tax_rate_versions = tax_rates
+            .into_iter()
+            .map(|tax_rate| (tax_rate._id, tax_rate.current_version))
+            .collect();
+        Ok(Self {
+            product_variant_versions,
+            tax_rate_versions,
+        })
+    }
+
+    /// Calculates the subtotal of a shopping cart item, excluding taxes.
+    ///
+    /// * `shoppingcart_item` - Shopping cart item to calculate the subtotal of.
+    pub fn subtotal(&self, shoppingcart_item: &ShoppingCartItem) -> Result<u64> {
+        let product_variant_version = self.product_variant_version(shoppingcart_item)?;
+        Ok(u64::from(shoppingcart_item.count) * u64::from(product_variant_version.retail_price))
+    }
+
+    /// Estimates the tax of a shopping cart item based on the current tax rate of its product variant.
+    ///
+    /// Rounds to the nearest whole amount.
+    ///
+    /// * `shoppingcart_item` - Shopping cart item to estimate the tax of.
+    pub fn estimated_tax(&self, shoppingcart_item: &ShoppingCartItem) -> Result<u64> {
+        let product_variant_version = self.product_variant_version(shoppingcart_item)?;
+        let tax_rate_version = self
+            .tax_rate_versions
+            .get(&product_variant_version.tax_rate_id)
+            .ok_or_else(|| {
+                let message = format!(
+                    "Tax rate with UUID: `{}` is not present in the system.",
+                    product_variant_version.tax_rate_id
+                );
+                ShoppingCartError::NotFound {
+                    message,
+                    id: Some(product_variant_version.tax_rate_id),
+                }
+            })?;
+        let subtotal = self.subtotal(shoppingcart_item)?;
+        Ok((subtotal as f64 * tax_rate_version.rate).round() as u64)
+    }
+
+    /// Calculates the discount granted on a shopping cart item by discounts.
+    ///
+    /// Discounts applying to the product variant of the shopping cart item are applied one after another to the remaining amount.
+    /// Rounds to the nearest whole amount.
+    ///
+    /// * `shoppingcart_item` - Shopping cart item to calculate the discount of.
+    /// * `discounts` - Discounts of the coupons applied to the shopping cart.
+    pub fn discount(
+        &self,
+        shoppingcart_item: &ShoppingCartItem,
+        discounts: &[Discount],
+    ) -> Result<u64> {
+        let subtotal = self.subtotal(shoppingcart_item)? as f64;
+        let discounted_subtotal = discounts
+            .iter()
+            .filter(|discount| discount.applies_to(&shoppingcart_item.product_variant))
+            .fold(subtotal, |amount, discount| {
+                amount * (1.0 - discount.discount.clamp(0.0, 1.0))
+            });
+        Ok((subtotal - discounted_subtotal).round() as u64)
+    }
+
+    /// Defines if the pricing contains the current product variant versions of all shopping cart items.
+    ///
+    /// * `shoppingcart_items` - Shopping cart items to check.
+    fn covers(&self, shoppingcart_items: &[&ShoppingCartItem]) -> bool {
+        shoppingcart_items.iter().all(|shoppingcart_item| {
+            self.product_variant_versions
+                .contains_key(&shoppingcart_item.product_variant._id)
+        })
+    }
+
+    /// Retrieves the current product variant version of the product variant of a shopping cart item.
+    ///
+    /// * `shoppingcart_item` - Shopping cart item referencing the product variant.
+    fn product_variant_version(
+        &self,
+        shoppingcart_item: &ShoppingCartItem,
+    ) -> Result<&ProductVariantVersion> {
+        self.product_variant_versions
+            .get(&shoppingcart_item.product_variant._id)
+            .ok_or_else(|| {
+                let message = format!(
+                    "Product variant with UUID: `{}` has no known retail price.",
+                    shoppingcart_item.product_variant._id
+                );
+                ShoppingCartError::NotFound {
+                    message,
+                    id: Some(shoppingcart_item.product_variant._id),
+                }
+                .into()
+            })
+    }
+}
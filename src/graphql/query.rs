@@ -1,21 +1,279 @@
-use std::any::type_name;
+use std::{
+    any::type_name,
+    collections::{HashMap, HashSet},
+};
 
-use async_graphql::{Context, Error, Object, Result};
+use async_graphql::{Context, Guard, Object, OutputType, Result};
 
-use bson::Uuid;
-use mongodb::{bson::doc, options::FindOneOptions, Collection, Database};
-use serde::Deserialize;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bson::{DateTime, Document, Uuid};
+use futures::TryStreamExt;
+use mongodb::{
+    bson::doc,
+    options::{FindOneOptions, FindOptions},
+    Collection, Database,
+};
+use mongodb_cursor_pagination::{FindResult, PaginatedCursor};
+use serde::{de::DeserializeOwned, Deserialize};
 
-use crate::authorization::authorize_user;
+use crate::{
+    authorization::{
+        authorized_user_id,
+        guard::{OwnerOrPermissive, RequirePermission, RequireRole},
+        permission::Permission,
+        Role,
+    },
+    error::ShoppingCartError,
+};
 
-use super::model::{shoppingcart::ShoppingCart, shoppingcart_item::ShoppingCartItem, user::User};
+use super::{
+    limits::ShoppingCartLimits,
+    model::{
+        audit_log_entry::AuditLogEntry,
+        checkout_readiness::CheckoutReadiness,
+        connection::{
+            audit_log_entry_connection::AuditLogEntryConnection,
+            base_connection::{BaseConnection, FindResultWrapper},
+            user_connection::UserConnection,
+        },
+        filter_datatypes::{
+            abandoned_shoppingcarts_filter, DateTimeRangeInput, ShoppingCartFilterInput,
+        },
+        foreign_types::{Discount, ProductVariant},
+        order_datatypes::ShoppingCartOrderInput,
+        product_variant_cart_statistics::ProductVariantCartStatistics,
+        shoppingcart::ShoppingCart,
+        shoppingcart_item::ShoppingCartItem,
+        shoppingcart_statistics::ShoppingCartStatistics,
+        user::User,
+    },
+};
+
+/// Amount of entities retrieved by paginated queries if `first` is not set.
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Maximum amount of entities a paginated query can retrieve at once.
+const MAX_PAGE_SIZE: usize = 100;
 
 /// Describes GraphQL shopping cart queries.
 pub struct Query;
 
 #[Object]
 impl Query {
+    /// Retrieves all users and their shopping carts.
+    ///
+    /// Only permitted for users allowed to read data of all users.
+    #[graphql(guard = "RequirePermission(Permission::ReadAll)")]
+    async fn shoppingcarts<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(
+            desc = "Describes that the `first` N shoppingcarts should be retrieved, `20` by default and at most `100`."
+        )]
+        first: Option<usize>,
+        #[graphql(
+            desc = "Describes that shoppingcarts after the shoppingcart with this cursor should be retrieved."
+        )]
+        after: Option<String>,
+        #[graphql(desc = "Specifies the order in which shoppingcarts are retrieved.")]
+        order_by: Option<ShoppingCartOrderInput>,
+        #[graphql(desc = "Specifies which shoppingcarts are retrieved.")] filter: Option<
+            ShoppingCartFilterInput,
+        >,
+    ) -> Result<UserConnection> {
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<Document> = db_client.collection::<Document>("users");
+        let filter_doc: Document = filter.unwrap_or_default().into();
+        query_paginated_users(&collection, first, after, order_by, filter_doc).await
+    }
+
+    /// Retrieves all users whose shopping carts contain a product variant.
+    ///
+    /// Only permitted for users allowed to read data of all users.
+    #[graphql(guard = "RequirePermission(Permission::ReadAll)")]
+    async fn shoppingcarts_containing_product_variant<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of product variant the shoppingcarts contain.")] id: Uuid,
+        #[graphql(
+            desc = "Describes that the `first` N shoppingcarts should be retrieved, `20` by default and at most `100`."
+        )]
+        first: Option<usize>,
+        #[graphql(
+            desc = "Describes that shoppingcarts after the shoppingcart with this cursor should be retrieved."
+        )]
+        after: Option<String>,
+        #[graphql(desc = "Specifies the order in which shoppingcarts are retrieved.")]
+        order_by: Option<ShoppingCartOrderInput>,
+    ) -> Result<UserConnection> {
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<Document> = db_client.collection::<Document>("users");
+        let filter_doc = doc! {"shoppingcart.internal_shoppingcart_items.product_variant._id": id};
+        query_paginated_users(&collection, first, after, order_by, filter_doc).await
+    }
+
+    /// Retrieves all users whose shopping carts were not updated since a timestamp.
+    ///
+    /// Only permitted for admins.
+    #[graphql(guard = "RequireRole(Role::Admin)")]
+    async fn abandoned_shoppingcarts<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "Shoppingcarts last updated before this timestamp are retrieved.")]
+        inactive_since: DateTime,
+        #[graphql(
+            desc = "Minimum amount of shoppingcart items of retrieved shoppingcarts, defaults to `1`."
+        )]
+        min_items: Option<usize>,
+        #[graphql(
+            desc = "Describes that the `first` N shoppingcarts should be retrieved, `20` by default and at most `100`."
+        )]
+        first: Option<usize>,
+        #[graphql(
+            desc = "Describes that shoppingcarts after the shoppingcart with this cursor should be retrieved."
+        )]
+        after: Option<String>,
+        #[graphql(desc = "Specifies the order in which shoppingcarts are retrieved.")]
+        order_by: Option<ShoppingCartOrderInput>,
+    ) -> Result<UserConnection> {
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<Document> = db_client.collection::<Document>("users");
+        let filter_doc = abandoned_shoppingcarts_filter(inactive_since, min_items.unwrap_or(1));
+        query_paginated_users(&collection, first, after, order_by, filter_doc).await
+    }
+
+    /// Retrieves aggregated statistics over all non-empty shopping carts.
+    ///
+    /// Only permitted for admins.
+    #[graphql(guard = "RequireRole(Role::Admin)")]
+    async fn shoppingcart_statistics<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "Only shoppingcarts last updated within this range are considered.")]
+        time_range: Option<DateTimeRangeInput>,
+        #[graphql(
+            desc = "Amount of product variants in the top product variant lists, between `1` and `100`, defaults to `10`."
+        )]
+        top: Option<usize>,
+    ) -> Result<ShoppingCartStatistics> {
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<User> = db_client.collection::<User>("users");
+        ShoppingCartStatistics::aggregate(&collection, time_range, top.unwrap_or(10)).await
+    }
+
+    /// Retrieves the audit log of mutations on shopping carts performed by users not owning the shopping cart, newest first.
+    ///
+    /// Only permitted for admins.
+    #[graphql(guard = "RequireRole(Role::Admin)")]
+    async fn audit_log<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(
+            desc = "Describes that the `first` N audit log entries should be retrieved, `20` by default and at most `100`."
+        )]
+        first: Option<usize>,
+        #[graphql(desc = "Cursor of the audit log entry after which entries are retrieved.")]
+        after: Option<String>,
+        #[graphql(desc = "Retrieves only entries of mutations on the shopping cart of this user.")]
+        target_user_id: Option<Uuid>,
+        #[graphql(desc = "Retrieves only entries of mutations performed by this user.")]
+        actor_id: Option<Uuid>,
+    ) -> Result<AuditLogEntryConnection> {
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<Document> = db_client.collection::<Document>("audit_log");
+        let mut filter_doc = Document::new();
+        if let Some(target_user_id) = target_user_id {
+            filter_doc.insert("target_user_id", target_user_id);
+        }
+        if let Some(actor_id) = actor_id {
+            filter_doc.insert("actor_id", actor_id);
+        }
+        let sorting_doc = doc! {"timestamp": -1, "_id": -1};
+        query_paginated::<AuditLogEntry, _>(
+            &collection,
+            first,
+            after,
+            sorting_doc,
+            filter_doc,
+            "audit log entries",
+        )
+        .await
+    }
+
+    /// Retrieves how many shopping carts contain a product variant and its total count over all shopping carts.
+    ///
+    /// Only permitted for users allowed to read data of all users.
+    #[graphql(guard = "RequirePermission(Permission::ReadAll)")]
+    async fn product_variant_cart_statistics<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of product variant to retrieve statistics of.")] id: Uuid,
+    ) -> Result<ProductVariantCartStatistics> {
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<User> = db_client.collection::<User>("users");
+        let product_variant_filter =
+            doc! {"shoppingcart.internal_shoppingcart_items.product_variant._id": id};
+        let pipeline = vec![
+            doc! {"$match": &product_variant_filter},
+            doc! {"$unwind": "$shoppingcart.internal_shoppingcart_items"},
+            doc! {"$match": &product_variant_filter},
+            doc! {"$group": {
+                "_id": "$_id",
+                "count": {"$sum": "$shoppingcart.internal_shoppingcart_items.count"}
+            }},
+            doc! {"$group": {
+                "_id": id,
+                "shoppingcart_count": {"$sum": 1},
+                "total_count": {"$sum": "$count"}
+            }},
+        ];
+        let message = format!(
+            "Retrieving statistics of product variant with UUID: `{}` failed in MongoDB.",
+            id
+        );
+        let statistics_docs: Vec<Document> = match collection.aggregate(pipeline, None).await {
+            Ok(cursor) => cursor
+                .try_collect()
+                .await
+                .map_err(|error| ShoppingCartError::storage(message.clone(), error))?,
+            Err(error) => return Err(ShoppingCartError::storage(message, error).into()),
+        };
+        match statistics_docs.into_iter().next() {
+            Some(statistics_doc) => Ok(bson::from_document(statistics_doc)?),
+            None => Ok(ProductVariantCartStatistics::empty(id)),
+        }
+    }
+
+    /// Retrieves the shopping cart of the user the request is authorized for.
+    #[graphql(guard = "OwnerOrPermissive::authorized_user()")]
+    async fn my_shoppingcart<'a>(&self, ctx: &Context<'a>) -> Result<ShoppingCart> {
+        let user_id = authorized_user_id(ctx)?;
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<User> = db_client.collection::<User>("users");
+        query_shoppingcart(&collection, user_id).await
+    }
+
+    /// Retrieves the users who invited the user the request is authorized for to their shopping carts.
+    #[graphql(guard = "OwnerOrPermissive::authorized_user()")]
+    async fn shoppingcarts_shared_with_me<'a>(&self, ctx: &Context<'a>) -> Result<Vec<User>> {
+        let user_id = authorized_user_id(ctx)?;
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<User> = db_client.collection::<User>("users");
+        let storage_error = |error| {
+            ShoppingCartError::storage("Retrieving shared shoppingcarts failed in MongoDB.", error)
+        };
+        match collection
+            .find(doc! {"shoppingcart.members.user_id": user_id}, None)
+            .await
+        {
+            Ok(cursor) => Ok(cursor.try_collect().await.map_err(storage_error)?),
+            Err(error) => Err(storage_error(error).into()),
+        }
+    }
+
     /// Entity resolver for user of specific UUID.
+    ///
+    /// The shopping cart of the user is guarded by `OwnerOrPermissive`.
     #[graphql(entity)]
     async fn user_entity_resolver<'a>(
         &self,
@@ -28,6 +286,7 @@ impl Query {
     }
 
     /// Retrieves shopping cart item of specific UUID.
+    #[graphql(guard = "OwnerOrPermissive::shoppingcart_item(id)")]
     async fn shoppingcart_item<'a>(
         &self,
         ctx: &Context<'a>,
@@ -36,22 +295,155 @@ impl Query {
         let db_client = ctx.data::<Database>()?;
         let collection: Collection<User> = db_client.collection::<User>("users");
         let user = query_shoppingcart_item_user(&collection, id).await?;
-        authorize_user(&ctx, Some(user._id))?;
         project_user_to_shopping_cart_item(user)
     }
 
     /// Entity resolver for shopping cart item of specific UUID.
+    ///
+    /// Only permitted for the owning user, users with a permissive role and the trusted internal gateway.
+    /// Authorizes before retrieving the shopping cart item, so other users cannot probe which shopping cart items exist.
     #[graphql(entity)]
     async fn shoppingcart_item_entity_resolver<'a>(
         &self,
         ctx: &Context<'a>,
         #[graphql(key, desc = "UUID of shoppingcart to retrieve.")] id: Uuid,
     ) -> Result<ShoppingCartItem> {
+        OwnerOrPermissive::shoppingcart_item(id).check(ctx).await?;
         let db_client = ctx.data::<Database>()?;
         let collection: Collection<User> = db_client.collection::<User>("users");
         let user = query_shoppingcart_item_user(&collection, id).await?;
         project_user_to_shopping_cart_item(user)
     }
+
+    /// Evaluates whether the shopping cart of a user can be checked out and lists all problems preventing it.
+    #[graphql(guard = "OwnerOrPermissive::user(user_id)")]
+    async fn checkout_readiness<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of user owning the shopping cart.")] user_id: Uuid,
+    ) -> Result<CheckoutReadiness> {
+        let db_client = ctx.data::<Database>()?;
+        let limits = ctx.data::<ShoppingCartLimits>()?;
+        let collection: Collection<User> = db_client.collection::<User>("users");
+        let product_variant_collection: Collection<ProductVariant> =
+            db_client.collection::<ProductVariant>("product_variants");
+        let shoppingcart = query_shoppingcart(&collection, user_id).await?;
+        let product_variants = query_product_variants(
+            &product_variant_collection,
+            &shoppingcart.internal_shoppingcart_items,
+        )
+        .await?;
+        Ok(CheckoutReadiness::evaluate(
+            &shoppingcart,
+            &product_variants,
+            limits,
+        ))
+    }
+}
+
+/// Retrieves a page of users matching a filter, ordered by a shopping cart order.
+///
+/// * `collection` - MongoDB collection of users.
+/// * `first` - Maximum amount of users to retrieve.
+/// * `after` - Cursor of the user after which users are retrieved.
+/// * `order_by` - Specifies the order in which users are retrieved.
+/// * `filter_doc` - MongoDB filter users have to match.
+async fn query_paginated_users(
+    collection: &Collection<Document>,
+    first: Option<usize>,
+    after: Option<String>,
+    order_by: Option<ShoppingCartOrderInput>,
+    filter_doc: Document,
+) -> Result<UserConnection> {
+    let shoppingcart_order = order_by.unwrap_or_default();
+    let field = shoppingcart_order.field.unwrap_or_default().as_str();
+    let direction = i32::from(shoppingcart_order.direction.unwrap_or_default());
+    let mut sorting_doc = doc! {field: direction};
+    sorting_doc.insert("_id", direction);
+    query_paginated(
+        collection,
+        first,
+        after,
+        sorting_doc,
+        filter_doc,
+        "shoppingcarts",
+    )
+    .await
+}
+
+/// Queries a page of entities from a MongoDB collection.
+///
+/// * `collection` - MongoDB collection of entities.
+/// * `first` - Amount of entities to retrieve, `DEFAULT_PAGE_SIZE` if not set.
+/// * `after` - Cursor of the entity after which entities are retrieved.
+/// * `sorting_doc` - MongoDB sort document, must contain `_id` to ensure stable pagination.
+/// * `filter_doc` - MongoDB query document entities need to match.
+/// * `entity_name` - Plural name of the entities, used in error messages.
+async fn query_paginated<Node, C>(
+    collection: &Collection<Document>,
+    first: Option<usize>,
+    after: Option<String>,
+    sorting_doc: Document,
+    filter_doc: Document,
+    entity_name: &str,
+) -> Result<C>
+where
+    Node: OutputType + DeserializeOwned + Sync + Send + Unpin + Clone,
+    C: From<BaseConnection<Node>>,
+{
+    if let Some(cursor) = &after {
+        validate_cursor(cursor)?;
+    }
+    let find_options = paginated_find_options(first, sorting_doc)?;
+    let maybe_find_results: Result<FindResult<Node>, _> =
+        PaginatedCursor::new(Some(find_options), after, None)
+            .find(collection, Some(&paginated_filter(filter_doc)))
+            .await;
+    match maybe_find_results {
+        Ok(find_results) => {
+            let find_result_wrapper = FindResultWrapper(find_results);
+            let connection = Into::<BaseConnection<Node>>::into(find_result_wrapper);
+            Ok(connection.into())
+        }
+        Err(error) => {
+            let message = format!("Retrieving {} failed in MongoDB.", entity_name);
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Builds the find options of a paginated query.
+///
+/// `PaginatedCursor` retrieves one additional entity itself to detect a next page, so the limit is exactly `first`.
+/// Fails with a validation error if `first` exceeds `MAX_PAGE_SIZE`.
+///
+/// * `first` - Amount of entities to retrieve, `DEFAULT_PAGE_SIZE` if not set.
+/// * `sorting_doc` - MongoDB sort document, must contain `_id` to ensure stable pagination.
+fn paginated_find_options(first: Option<usize>, sorting_doc: Document) -> Result<FindOptions> {
+    let page_size = first.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page_size > MAX_PAGE_SIZE {
+        let message = format!(
+            "`first` is set to `{}`, which exceeds the maximum page size of `{}`.",
+            page_size, MAX_PAGE_SIZE
+        );
+        return Err(ShoppingCartError::Validation { message, id: None }.into());
+    }
+    Ok(FindOptions::builder()
+        .limit(page_size as i64)
+        .sort(sorting_doc)
+        .build())
+}
+
+/// Wraps the filter of a paginated query, so the conditions `PaginatedCursor` adds for a cursor cannot overwrite it.
+///
+/// `PaginatedCursor` inserts its conditions on the sorted fields into the filter, replacing conditions on the same fields.
+///
+/// * `filter_doc` - MongoDB query document entities need to match.
+fn paginated_filter(filter_doc: Document) -> Document {
+    match filter_doc.is_empty() {
+        true => filter_doc,
+        false => doc! {"$and": [filter_doc]},
+    }
 }
 
 /// Shared function to query a shopping cart from a MongoDB collection of shopping carts.
@@ -64,12 +456,19 @@ pub async fn query_shoppingcart(collection: &Collection<User>, id: Uuid) -> Resu
             Some(user) => Ok(user.shoppingcart),
             None => {
                 let message = format!("ShoppingCart with UUID: `{}` not found.", id);
-                Err(Error::new(message))
+                Err(ShoppingCartError::NotFound {
+                    message,
+                    id: Some(id),
+                }
+                .into())
             }
         },
-        Err(_) => {
-            let message = format!("ShoppingCart with UUID: `{}` not found.", id);
-            Err(Error::new(message))
+        Err(error) => {
+            let message = format!(
+                "Retrieving ShoppingCart with UUID: `{}` failed in MongoDB.",
+                id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
         }
     }
 }
@@ -99,8 +498,17 @@ pub async fn query_shoppingcart_item_user(collection: &Collection<User>, id: Uui
         )
         .await
     {
-        Ok(maybe_user) => maybe_user.ok_or(Error::new(message.clone())),
-        Err(e) => Err(e.into()),
+        Ok(maybe_user) => Ok(maybe_user.ok_or(ShoppingCartError::NotFound {
+            message,
+            id: Some(id),
+        })?),
+        Err(error) => {
+            let message = format!(
+                "Retrieving ShoppingCartItem of UUID: `{}` failed in MongoDB.",
+                id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
     }
 }
 
@@ -108,29 +516,25 @@ pub async fn query_shoppingcart_item_user(collection: &Collection<User>, id: Uui
 ///
 /// * `user` - User to project to shoppingc art items.
 pub fn project_user_to_shopping_cart_item(user: User) -> Result<ShoppingCartItem> {
-    let message = format!("Projection failed, shoppingcart item could not be extracted from user.");
+    let message = "Projection failed, shoppingcart item could not be extracted from user.";
     user.shoppingcart
         .internal_shoppingcart_items
         .iter()
         .next()
         .cloned()
-        .ok_or(Error::new(message.clone()))
-}
-
-/// Queries shopping cart item user and applies projection directly.
-///
-/// * `connection` - MongoDB database connection.
-/// * `id` - UUID of user.
-pub async fn query_shoppingcart_item(
-    collection: &Collection<User>,
-    id: Uuid,
-) -> Result<ShoppingCartItem> {
-    let user = query_shoppingcart_item_user(&collection, id).await?;
-    project_user_to_shopping_cart_item(user)
+        .ok_or(
+            ShoppingCartError::NotFound {
+                message: message.to_string(),
+                id: None,
+            }
+            .into(),
+        )
 }
 
 /// Queries shopping cart item user by a product variant UUID and user UUID and applies projection directly.
 ///
+/// Returns `None` if the shopping cart does not contain a shopping cart item referencing the product variant.
+///
 /// * `connection` - MongoDB database connection.
 /// * `product_variant_id` - UUID of product variant.
 /// * `id` - UUID of user.
@@ -138,18 +542,20 @@ pub async fn query_shoppingcart_item_by_product_variant_id_and_user_id(
     collection: &Collection<User>,
     product_variant_id: Uuid,
     user_id: Uuid,
-) -> Result<ShoppingCartItem> {
-    let user = query_shoppingcart_item_user_by_product_variant_id_and_user_id(
-        &collection,
+) -> Result<Option<ShoppingCartItem>> {
+    let maybe_user = query_shoppingcart_item_user_by_product_variant_id_and_user_id(
+        collection,
         product_variant_id,
         user_id,
     )
     .await?;
-    project_user_to_shopping_cart_item(user)
+    maybe_user
+        .map(project_user_to_shopping_cart_item)
+        .transpose()
 }
 
 /// Shared function to query a shopping cart item from a MongoDB collection of users by a product variant UUID and user UUID.
-/// Returns user which only contains the queried shopping cart item.
+/// Returns user which only contains the queried shopping cart item, `None` if no shopping cart item references the product variant.
 ///
 /// * `connection` - MongoDB database connection.
 /// * `product_variant_id` - UUID of product variant.
@@ -158,14 +564,14 @@ pub async fn query_shoppingcart_item_user_by_product_variant_id_and_user_id(
     collection: &Collection<User>,
     product_variant_id: Uuid,
     user_id: Uuid,
-) -> Result<User> {
+) -> Result<Option<User>> {
     let find_options = FindOneOptions::builder()
         .projection(Some(doc! {
             "shoppingcart.internal_shoppingcart_items.$": 1,
-            "_id": 0
+            "shoppingcart.last_updated_at": 1,
+            "_id": 1
         }))
         .build();
-    let message = format!("ShoppingCartItem referencing product variant of UUID: `{}` in shopping cart of user with UUID: `{}` not found.", product_variant_id, user_id);
     match collection
         .find_one(
             doc! {"_id": user_id, "shoppingcart.internal_shoppingcart_items": {
@@ -177,8 +583,11 @@ pub async fn query_shoppingcart_item_user_by_product_variant_id_and_user_id(
         )
         .await
     {
-        Ok(maybe_user) => maybe_user.ok_or(Error::new(message.clone())),
-        Err(_) => Err(Error::new(message)),
+        Ok(maybe_user) => Ok(maybe_user),
+        Err(error) => {
+            let message = format!("Retrieving ShoppingCartItem referencing product variant of UUID: `{}` in shopping cart of user with UUID: `{}` failed in MongoDB.", product_variant_id, user_id);
+            Err(ShoppingCartError::storage(message, error).into())
+        }
     }
 }
 
@@ -195,12 +604,194 @@ pub async fn query_object<T: for<'a> Deserialize<'a> + Unpin + Send + Sync>(
             Some(object) => Ok(object),
             None => {
                 let message = format!("{} with UUID: `{}` not found.", type_name::<T>(), id);
-                Err(Error::new(message))
+                Err(ShoppingCartError::NotFound {
+                    message,
+                    id: Some(id),
+                }
+                .into())
             }
         },
-        Err(_) => {
-            let message = format!("{} with UUID: `{}` not found.", type_name::<T>(), id);
-            Err(Error::new(message))
+        Err(error) => {
+            let message = format!(
+                "Retrieving {} with UUID: `{}` failed in MongoDB.",
+                type_name::<T>(),
+                id
+            );
+            Err(ShoppingCartError::storage(message, error).into())
+        }
+    }
+}
+
+/// Queries the discounts of coupon codes which are currently valid.
+///
+/// Coupon codes without a discount in the system or with an expired discount are ignored.
+///
+/// * `db_client` - MongoDB database containing the discount projection.
+/// * `codes` - Coupon codes to query discounts of.
+pub async fn query_valid_discounts(
+    db_client: &Database,
+    codes: &HashSet<String>,
+) -> Result<Vec<Discount>> {
+    let collection: Collection<Discount> = db_client.collection::<Discount>("discounts");
+    let current_timestamp = DateTime::now();
+    let storage_error =
+        |error| ShoppingCartError::storage("Retrieving discounts failed in MongoDB.", error);
+    let discounts: Vec<Discount> = collection
+        .find(
+            doc! {"code": { "$in": codes.iter().collect::<Vec<&String>>() } },
+            None,
+        )
+        .await
+        .map_err(storage_error)?
+        .try_collect()
+        .await
+        .map_err(storage_error)?;
+    Ok(discounts
+        .into_iter()
+        .filter(|discount| discount.is_valid_at(current_timestamp))
+        .collect())
+}
+
+/// Queries the product variants referenced by shopping cart items.
+///
+/// Returns the product variants present in the system by product variant UUID.
+///
+/// * `collection` - MongoDB collection of product variants.
+/// * `shoppingcart_items` - Shopping cart items referencing the product variants.
+pub async fn query_product_variants<'a>(
+    collection: &Collection<ProductVariant>,
+    shoppingcart_items: impl IntoIterator<Item = &'a ShoppingCartItem>,
+) -> Result<HashMap<Uuid, ProductVariant>> {
+    let product_variant_ids: Vec<Uuid> = shoppingcart_items
+        .into_iter()
+        .map(|shoppingcart_item| shoppingcart_item.product_variant._id)
+        .collect();
+    let storage_error =
+        |error| ShoppingCartError::storage("Retrieving product variants failed in MongoDB.", error);
+    let product_variants: Vec<ProductVariant> = collection
+        .find(doc! {"_id": { "$in": &product_variant_ids } }, None)
+        .await
+        .map_err(storage_error)?
+        .try_collect()
+        .await
+        .map_err(storage_error)?;
+    Ok(product_variants
+        .into_iter()
+        .map(|product_variant| (product_variant._id, product_variant))
+        .collect())
+}
+
+/// Checks if a pagination cursor can be decoded and contains an `_id`, as `PaginatedCursor` panics otherwise.
+///
+/// * `cursor` - Base64 encoded BSON document describing the position of an entity.
+fn validate_cursor(cursor: &str) -> Result<()> {
+    STANDARD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| bson::from_slice::<Document>(&bytes).ok())
+        .filter(|cursor_doc| cursor_doc.contains_key("_id"))
+        .map(|_| ())
+        .ok_or_else(|| {
+            ShoppingCartError::Validation {
+                message: format!("Cursor: `{}` is invalid.", cursor),
+                id: None,
+            }
+            .into()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use bson::Bson;
+
+    use crate::graphql::model::order_datatypes::{OrderDirection, ShoppingCartOrderField};
+
+    use super::*;
+
+    #[test]
+    fn paginated_find_options_limit_to_first() {
+        let find_options = paginated_find_options(Some(2), doc! {"_id": 1}).unwrap();
+        assert_eq!(find_options.limit, Some(2));
+        let find_options = paginated_find_options(None, doc! {"_id": 1}).unwrap();
+        assert_eq!(find_options.limit, Some(DEFAULT_PAGE_SIZE as i64));
+        let find_options = paginated_find_options(Some(MAX_PAGE_SIZE), doc! {"_id": 1}).unwrap();
+        assert_eq!(find_options.limit, Some(MAX_PAGE_SIZE as i64));
+        let error = paginated_find_options(Some(MAX_PAGE_SIZE + 1), doc! {"_id": 1}).unwrap_err();
+        assert_eq!(
+            error.extensions.unwrap().get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+    }
+
+    #[test]
+    fn paginated_filter_survives_cursor_conditions() {
+        let updated_since = DateTime::from_millis(1_000);
+        let filter_doc = doc! {"shoppingcart.last_updated_at": {"$gte": updated_since}};
+        let mut query = paginated_filter(filter_doc.clone());
+        query.insert(
+            "shoppingcart.last_updated_at",
+            doc! {"$gt": DateTime::from_millis(500)},
+        );
+        assert_eq!(
+            query.get("$and"),
+            Some(&Bson::Array(vec![Bson::Document(filter_doc)]))
+        );
+        assert_eq!(paginated_filter(Document::new()), Document::new());
+    }
+
+    /// Pages through users filtered by `updatedSince` against the MongoDB at `$MONGODB_TEST_URI`.
+    #[tokio::test]
+    #[ignore = "requires a MongoDB at $MONGODB_TEST_URI"]
+    async fn query_paginated_users_pages_past_first_page() {
+        let uri = env::var("MONGODB_TEST_URI").expect("$MONGODB_TEST_URI is not set.");
+        let client = mongodb::Client::with_uri_str(uri).await.unwrap();
+        let collection: Collection<Document> = client
+            .database("shoppingcart-test-database")
+            .collection(&format!("users-{}", Uuid::new()));
+        let users: Vec<Document> = (0..5)
+            .map(|index| {
+                let mut shoppingcart = ShoppingCart::new();
+                shoppingcart.last_updated_at = DateTime::from_millis(1_000 * index);
+                bson::to_document(&User {
+                    _id: Uuid::new(),
+                    shoppingcart,
+                })
+                .unwrap()
+            })
+            .collect();
+        collection.insert_many(users, None).await.unwrap();
+        let filter_doc =
+            doc! {"shoppingcart.last_updated_at": {"$gte": DateTime::from_millis(1_000)}};
+        let mut after = None;
+        let mut last_updated_ats = Vec::new();
+        loop {
+            let page = query_paginated_users(
+                &collection,
+                Some(1),
+                after,
+                Some(ShoppingCartOrderInput {
+                    direction: Some(OrderDirection::Asc),
+                    field: Some(ShoppingCartOrderField::LastUpdatedAt),
+                }),
+                filter_doc.clone(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(page.total_count, 4);
+            assert!(page.nodes.len() <= 1);
+            last_updated_ats.extend(
+                page.nodes
+                    .iter()
+                    .map(|user| user.shoppingcart.last_updated_at.timestamp_millis()),
+            );
+            if !page.has_next_page {
+                break;
+            }
+            after = page.end_cursor;
         }
+        collection.drop(None).await.unwrap();
+        assert_eq!(last_updated_ats, vec![1_000, 2_000, 3_000, 4_000]);
     }
 }
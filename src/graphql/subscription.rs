@@ -0,0 +1,81 @@
+use async_graphql::{Context, Result, Subscription as GraphQLSubscription};
+use bson::Uuid;
+use futures::{stream, Stream, StreamExt};
+use mongodb::{Collection, Database};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    authorization::guard::OwnerOrPermissive,
+    event::shoppingcart_change_feed::{ShoppingCartChange, ShoppingCartChangeKind},
+};
+
+use super::{
+    model::{shoppingcart::ShoppingCart, user::User},
+    query::query_shoppingcart,
+};
+
+/// Capacity of the shopping cart update channel, slower subscribers skip to the latest shopping cart.
+const SHOPPINGCART_UPDATE_CAPACITY: usize = 1024;
+
+/// Broadcasts changes of shopping carts.
+#[derive(Clone)]
+pub struct ShoppingCartUpdates {
+    sender: broadcast::Sender<ShoppingCartChange>,
+}
+
+impl ShoppingCartUpdates {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(SHOPPINGCART_UPDATE_CAPACITY);
+        Self { sender }
+    }
+
+    /// Notifies subscribers that a shopping cart changed.
+    ///
+    /// * `shoppingcart_change` - Change of the shopping cart.
+    pub fn notify(&self, shoppingcart_change: ShoppingCartChange) {
+        // Sending only fails if there are no subscribers, in which case there is nobody to notify.
+        let _ = self.sender.send(shoppingcart_change);
+    }
+
+    /// Subscribes to changes of shopping carts.
+    pub fn subscribe(&self) -> broadcast::Receiver<ShoppingCartChange> {
+        self.sender.subscribe()
+    }
+}
+
+/// Describes GraphQL shopping cart subscriptions.
+pub struct Subscription;
+
+#[GraphQLSubscription]
+impl Subscription {
+    /// Emits the shopping cart of a user whenever it changes.
+    ///
+    /// Completes when the user is deleted.
+    #[graphql(guard = "OwnerOrPermissive::user(user_id)")]
+    async fn shoppingcart_updated<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of user owning the shopping cart.")] user_id: Uuid,
+    ) -> Result<impl Stream<Item = Result<ShoppingCart>>> {
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<User> = db_client.collection::<User>("users");
+        let receiver = ctx.data::<ShoppingCartUpdates>()?.subscribe();
+        let updates = stream::unfold(receiver, move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(shoppingcart_change) if shoppingcart_change.user_id != user_id => continue,
+                    Ok(ShoppingCartChange {
+                        kind: ShoppingCartChangeKind::Deleted,
+                        ..
+                    })
+                    | Err(RecvError::Closed) => return None,
+                    Ok(_) | Err(RecvError::Lagged(_)) => return Some(((), receiver)),
+                }
+            }
+        });
+        Ok(updates.then(move |_| {
+            let collection = collection.clone();
+            async move { query_shoppingcart(&collection, user_id).await }
+        }))
+    }
+}
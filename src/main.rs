@@ -1,21 +1,31 @@
-use std::{env, fs::File, io::Write};
+use std::{env, fs::File, io::Write, sync::Arc};
 
 use async_graphql::{
-    extensions::Logger, http::GraphiQLSource, EmptySubscription, SDLExportOptions, Schema,
+    extensions::Logger,
+    http::{GraphiQLSource, ALL_WEBSOCKET_PROTOCOLS},
+    Data, SDLExportOptions, Schema, ServerError,
 };
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 
-use authorization::AuthorizedUserHeader;
+use authorization::{authenticate, init_authorization, Impersonation, TrustedGateway};
 use axum::{
-    extract::State,
+    extract::{State, WebSocketUpgrade},
     http::{HeaderMap, StatusCode},
     response::{self, IntoResponse},
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
-use clap::{arg, command, Parser};
+use clap::Parser;
 use event::http_event_service::{
-    list_topic_subscriptions, on_order_creation_event, on_topic_event, HttpEventServiceState,
+    list_topic_subscriptions, on_discount_creation_event, on_discount_deletion_event,
+    on_discount_update_event, on_order_creation_event, on_product_variant_stock_update_event,
+    on_product_variant_update_event, on_product_variant_version_creation_event,
+    on_tax_rate_version_creation_event, on_topic_event, HttpEventServiceState,
+};
+use event::{
+    abandoned_shoppingcart_job::{run_abandoned_shoppingcart_job, AbandonedShoppingCartJobConfig},
+    dapr_publisher::DaprPublisher,
+    shoppingcart_change_feed::watch_shoppingcart_changes,
 };
 
 use once_cell::sync::Lazy;
@@ -27,20 +37,40 @@ use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider, Temporality};
 use opentelemetry_sdk::Resource;
 use opentelemetry_otlp::WithExportConfig;
 
-use log::{info, Level};
-use mongodb::{options::ClientOptions, Client, Database};
+use log::{error, info, Level};
+use mongodb::{bson::doc, options::ClientOptions, Client, Database, IndexModel};
 
 mod authorization;
+mod error;
 mod event;
 mod graphql;
+mod rate_limit;
 
-use graphql::model::{foreign_types::ProductVariant, user::User};
+use graphql::model::{
+    audit_log_entry::AuditLogEntry,
+    foreign_types::{Discount, ProductVariant, TaxRate},
+    user::User,
+};
 
-use crate::graphql::{mutation::Mutation, query::Query};
+use rate_limit::{run_rate_limit_eviction, RateLimit, RateLimiter};
+
+use crate::graphql::{
+    audit::record_impersonation,
+    limits::ShoppingCartLimits,
+    mutation::Mutation,
+    pricing::PricingCache,
+    query::Query,
+    subscription::{ShoppingCartUpdates, Subscription},
+};
 
 /// Builds the GraphiQL frontend.
 async fn graphiql() -> impl IntoResponse {
-    response::Html(GraphiQLSource::build().endpoint("/").finish())
+    response::Html(
+        GraphiQLSource::build()
+            .endpoint("/")
+            .subscription_endpoint("/ws")
+            .finish(),
+    )
 }
 
 /// Establishes database connection and returns the client.
@@ -60,6 +90,36 @@ async fn db_connection() -> Client {
     Client::with_options(client_options).unwrap()
 }
 
+/// Creates the MongoDB indexes queries of the service rely on.
+///
+/// * `db_client` - MongoDB database client.
+async fn create_indexes(db_client: &Database) {
+    let user_collection: mongodb::Collection<User> = db_client.collection::<User>("users");
+    let product_variant_index = IndexModel::builder()
+        .keys(doc! {"shoppingcart.internal_shoppingcart_items.product_variant._id": 1})
+        .build();
+    user_collection
+        .create_index(product_variant_index, None)
+        .await
+        .unwrap();
+    let member_index = IndexModel::builder()
+        .keys(doc! {"shoppingcart.members.user_id": 1})
+        .build();
+    user_collection
+        .create_index(member_index, None)
+        .await
+        .unwrap();
+    let audit_log_collection: mongodb::Collection<AuditLogEntry> =
+        db_client.collection::<AuditLogEntry>("audit_log");
+    let timestamp_index = IndexModel::builder()
+        .keys(doc! {"timestamp": -1, "_id": -1})
+        .build();
+    audit_log_collection
+        .create_index(timestamp_index, None)
+        .await
+        .unwrap();
+}
+
 /// Returns Router that establishes connection to Dapr.
 ///
 /// Adds endpoints to define pub/sub interaction with Dapr.
@@ -69,17 +129,47 @@ async fn build_dapr_router(db_client: Database) -> Router {
     let product_variant_collection: mongodb::Collection<ProductVariant> =
         db_client.collection::<ProductVariant>("product_variants");
     let user_collection: mongodb::Collection<User> = db_client.collection::<User>("users");
+    let tax_rate_collection: mongodb::Collection<TaxRate> =
+        db_client.collection::<TaxRate>("tax_rates");
+    let discount_collection: mongodb::Collection<Discount> =
+        db_client.collection::<Discount>("discounts");
 
     // Define routes.
-    let app = Router::new()
+    Router::new()
         .route("/dapr/subscribe", get(list_topic_subscriptions))
         .route("/on-order-creation-event", post(on_order_creation_event))
         .route("/on-topic-event", post(on_topic_event))
+        .route(
+            "/on-product-variant-version-creation-event",
+            post(on_product_variant_version_creation_event),
+        )
+        .route(
+            "/on-tax-rate-version-creation-event",
+            post(on_tax_rate_version_creation_event),
+        )
+        .route(
+            "/on-discount-creation-event",
+            post(on_discount_creation_event),
+        )
+        .route("/on-discount-update-event", post(on_discount_update_event))
+        .route(
+            "/on-discount-deletion-event",
+            post(on_discount_deletion_event),
+        )
+        .route(
+            "/on-product-variant-update-event",
+            post(on_product_variant_update_event),
+        )
+        .route(
+            "/on-product-variant-stock-update-event",
+            post(on_product_variant_stock_update_event),
+        )
         .with_state(HttpEventServiceState {
             product_variant_collection,
             user_collection,
-        });
-    app
+            tax_rate_collection,
+            discount_collection,
+        })
 }
 
 /// Command line argument to toggle schema generation instead of service execution.
@@ -98,7 +188,7 @@ async fn main() -> std::io::Result<()> {
 
     let args = Args::parse();
     if args.generate_schema {
-        let schema = Schema::build(Query, Mutation, EmptySubscription).finish();
+        let schema = Schema::build(Query, Mutation, Subscription).finish();
         let mut file = File::create("./schemas/shoppingcart.graphql")?;
         let sdl_export_options = SDLExportOptions::new().federation();
         let schema_sdl = schema.sdl_with_options(sdl_export_options);
@@ -112,24 +202,102 @@ async fn main() -> std::io::Result<()> {
 
 /// Describes the handler for GraphQL requests.
 ///
-/// Parses the `Authorized-User` header and writes it in the context data of the specfic request.
+/// Authenticates the user of the request and parses the impersonation and trusted gateway marker, then writes them in the context data of the specfic request.
+/// Responds with an error if the authentication or impersonation fails or an impersonated request contains mutations.
+/// The rate limit of the user is checked by the `RateLimit` schema extension.
+/// Records impersonated requests in the audit log.
 /// Then executes the GraphQL schema with the request.
 ///
 /// * `schema` - GraphQL schema used by handler.
+/// * `db_client` - MongoDB database client.
 /// * `headers` - Header map containing headers of request.
 /// * `request` - GraphQL request.
 async fn graphql_handler(
-    State(schema): State<Schema<Query, Mutation, EmptySubscription>>,
+    State(schema): State<Schema<Query, Mutation, Subscription>>,
+    Extension(db_client): Extension<Database>,
     headers: HeaderMap,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
     let mut req = req.into_inner();
-    if let Ok(authenticate_user_header) = AuthorizedUserHeader::try_from(&headers) {
+    let authenticate_user_header = match authenticate(&headers) {
+        Ok(authenticate_user_header) => authenticate_user_header,
+        Err(error) => return error_response(error),
+    };
+    match Impersonation::from_headers(&headers, authenticate_user_header.as_ref()) {
+        Ok(Some(impersonation)) => {
+            if let Err(error) = impersonation.check_read_only(&req.query) {
+                return error_response(error);
+            }
+            if let Some(actor) = &authenticate_user_header {
+                if let Err(error) =
+                    record_impersonation(&db_client, actor, &impersonation, &req.query).await
+                {
+                    return error_response(error);
+                }
+            }
+            req = req.data(impersonation);
+        }
+        Ok(None) => {}
+        Err(error) => return error_response(error),
+    }
+    if let Some(authenticate_user_header) = authenticate_user_header {
         req = req.data(authenticate_user_header);
     }
+    if let Some(trusted_gateway) = TrustedGateway::from_headers(&headers) {
+        req = req.data(trusted_gateway);
+    }
+    req = req.data(PricingCache::default());
     schema.execute(req).await.into()
 }
 
+/// Builds a GraphQL response containing only an error.
+///
+/// * `error` - Error to respond with.
+fn error_response(error: async_graphql::Error) -> GraphQLResponse {
+    let mut server_error = ServerError::new(error.message, None);
+    server_error.extensions = error.extensions;
+    async_graphql::Response::from_errors(vec![server_error]).into()
+}
+
+/// Describes the handler for GraphQL subscriptions over WebSocket.
+///
+/// Authenticates the user of the upgrade request and parses the trusted gateway marker, then writes them in the context data of the connection.
+/// Every operation sent over the connection is rate limited by the `RateLimit` schema extension.
+/// Rejects the upgrade if the authentication fails.
+/// Rejects impersonation, as mutations sent over the connection cannot be checked before the upgrade.
+///
+/// * `schema` - GraphQL schema used by handler.
+/// * `protocol` - GraphQL WebSocket protocol negotiated with the client.
+/// * `headers` - Header map containing headers of upgrade request.
+/// * `websocket` - WebSocket upgrade of the request.
+async fn graphql_ws_handler(
+    State(schema): State<Schema<Query, Mutation, Subscription>>,
+    protocol: GraphQLProtocol,
+    headers: HeaderMap,
+    websocket: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let mut data = Data::default();
+    match authenticate(&headers) {
+        Ok(Some(authenticate_user_header)) => data.insert(authenticate_user_header),
+        Ok(None) => {}
+        Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
+    }
+    if headers.contains_key("Impersonate-User") {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if let Some(trusted_gateway) = TrustedGateway::from_headers(&headers) {
+        data.insert(trusted_gateway);
+    }
+    websocket
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| {
+            GraphQLWebSocket::new(stream, schema, protocol)
+                .with_data(data)
+                .serve()
+        })
+        .into_response()
+}
+
 static RESOURCE: Lazy<Resource> = Lazy::new(|| {
     Resource::builder()
         .with_service_name("shoppingcart")
@@ -168,20 +336,56 @@ fn init_otlp() -> HttpMetricsLayer {
         .build()
 }
 
+/// Logs an invalid configuration read from the environment and exits the service.
+///
+/// * `message` - Description of the invalid configuration.
+fn exit_on_invalid_configuration<T>(message: String) -> T {
+    error!("Invalid configuration: {}", message);
+    std::process::exit(1)
+}
+
 /// Starts shoppingcart service on port 8000.
 async fn start_service() {
+    let limits = ShoppingCartLimits::from_env().unwrap_or_else(exit_on_invalid_configuration);
+    let abandoned_shoppingcart_job_config =
+        AbandonedShoppingCartJobConfig::from_env().unwrap_or_else(exit_on_invalid_configuration);
+    let rate_limiter =
+        Arc::new(RateLimiter::from_env().unwrap_or_else(exit_on_invalid_configuration));
     let client = db_connection().await;
     let db_client: Database = client.database("shoppingcart-database");
-
-    let schema = Schema::build(Query, Mutation, EmptySubscription)
+    if init_authorization() {
+        info!("Verifying bearer JWTs, Authorized-User headers are only accepted along matching tokens.");
+    }
+    create_indexes(&db_client).await;
+    let publisher = DaprPublisher::from_env();
+    tokio::spawn(run_abandoned_shoppingcart_job(
+        db_client.collection::<User>("users"),
+        publisher.clone(),
+        abandoned_shoppingcart_job_config,
+    ));
+    tokio::spawn(run_rate_limit_eviction(rate_limiter.clone()));
+    let shoppingcart_updates = ShoppingCartUpdates::new();
+    tokio::spawn(watch_shoppingcart_changes(
+        db_client.clone(),
+        shoppingcart_updates.clone(),
+        publisher,
+    ));
+
+    let schema = Schema::build(Query, Mutation, Subscription)
         .extension(Logger)
+        .extension(RateLimit)
         .data(db_client.clone())
+        .data(limits)
+        .data(shoppingcart_updates.clone())
+        .data(rate_limiter.clone())
         .enable_federation()
         .finish();
 
     let graphiql = Router::new()
         .route("/", get(graphiql).post(graphql_handler))
+        .route("/ws", get(graphql_ws_handler))
         .route("/health", get(StatusCode::OK))
+        .layer(Extension(db_client.clone()))
         .with_state(schema);
     let dapr_router = build_dapr_router(db_client).await;
     let metrics = init_otlp();
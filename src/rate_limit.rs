@@ -0,0 +1,570 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery},
+    parser::types::{ExecutableDocument, OperationType},
+    Pos, Result, ServerResult, Variables,
+};
+use bson::Uuid;
+
+use crate::{
+    authorization::{permission::Permission, AuthorizedUserHeader},
+    error::ShoppingCartError,
+    graphql::limits::env_var_or,
+};
+
+/// Maximum amount of retained buckets, the least recently used bucket is dropped when a bucket of another user is added.
+const MAX_RETAINED_BUCKETS: usize = 10_000;
+
+/// Interval in which buckets idle long enough to be full again are dropped, as they are equivalent to new buckets.
+const IDLE_EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Kind of GraphQL operation, each kind is limited with a separate budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperationKind {
+    /// Queries and subscriptions.
+    Query,
+    /// Mutations.
+    Mutation,
+}
+
+/// Budget of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitBudget {
+    /// Maximum amount of tokens, which is the maximum burst of requests.
+    pub capacity: f64,
+    /// Amount of tokens refilled per second, which is the sustained rate of requests.
+    pub refill_per_second: f64,
+}
+
+impl RateLimitBudget {
+    /// Duration after which an empty bucket is full again, `None` if it is never refilled.
+    fn full_refill_duration(&self) -> Option<Duration> {
+        Duration::try_from_secs_f64(self.capacity / self.refill_per_second).ok()
+    }
+}
+
+/// Token bucket of a user for a kind of operation.
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    /// Amount of tokens currently available.
+    tokens: f64,
+    /// Instant the tokens were last refilled.
+    refilled_at: Instant,
+    /// Sequence number of the last use, key of the bucket in the recency index.
+    last_use: u64,
+}
+
+impl TokenBucket {
+    /// Refills the tokens for the time passed since the last refill.
+    ///
+    /// * `budget` - Budget of the bucket.
+    /// * `now` - Current instant.
+    fn refill(&mut self, budget: &RateLimitBudget, now: Instant) {
+        let elapsed_seconds = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed_seconds * budget.refill_per_second).min(budget.capacity);
+        self.refilled_at = now;
+    }
+}
+
+/// Token buckets by user UUID and kind of operation, indexed by recency of use.
+#[derive(Debug, Default)]
+struct TokenBuckets {
+    /// Token buckets by user UUID and kind of operation.
+    buckets: HashMap<(Uuid, OperationKind), TokenBucket>,
+    /// Keys of the buckets by sequence number of their last use, least recently used first.
+    recency: BTreeMap<u64, (Uuid, OperationKind)>,
+    /// Sequence number of the next use.
+    next_use: u64,
+}
+
+impl TokenBuckets {
+    /// Retrieves the bucket of a key and marks it as most recently used.
+    ///
+    /// Creates a full bucket if none exists, dropping the least recently used bucket if `MAX_RETAINED_BUCKETS` are retained.
+    ///
+    /// * `key` - User UUID and kind of operation of the bucket.
+    /// * `budget` - Budget of the bucket.
+    /// * `now` - Current instant.
+    fn use_bucket(
+        &mut self,
+        key: (Uuid, OperationKind),
+        budget: &RateLimitBudget,
+        now: Instant,
+    ) -> &mut TokenBucket {
+        let last_use = self.next_use;
+        self.next_use += 1;
+        match self.buckets.get(&key) {
+            Some(bucket) => {
+                self.recency.remove(&bucket.last_use);
+            }
+            None if self.buckets.len() >= MAX_RETAINED_BUCKETS => {
+                if let Some((_, least_recently_used_key)) = self.recency.pop_first() {
+                    self.buckets.remove(&least_recently_used_key);
+                }
+            }
+            None => {}
+        }
+        self.recency.insert(last_use, key);
+        let bucket = self.buckets.entry(key).or_insert(TokenBucket {
+            tokens: budget.capacity,
+            refilled_at: now,
+            last_use,
+        });
+        bucket.last_use = last_use;
+        bucket
+    }
+}
+
+/// Token-bucket rate limiter of requests per user, with separate budgets for queries and mutations.
+///
+/// Users whose roles grant permissions on data of all users are exempt.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Budget of queries per user.
+    query_budget: RateLimitBudget,
+    /// Budget of mutations per user.
+    mutation_budget: RateLimitBudget,
+    /// Duration after which an unused bucket of any kind is full again, `None` if buckets of a kind are never refilled.
+    idle_eviction_after: Option<Duration>,
+    /// Token buckets by user UUID and kind of operation.
+    buckets: Mutex<TokenBuckets>,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter with budgets for queries and mutations.
+    ///
+    /// * `query_budget` - Budget of queries per user.
+    /// * `mutation_budget` - Budget of mutations per user.
+    pub fn new(query_budget: RateLimitBudget, mutation_budget: RateLimitBudget) -> Self {
+        let idle_eviction_after = query_budget
+            .full_refill_duration()
+            .zip(mutation_budget.full_refill_duration())
+            .map(|(query_duration, mutation_duration)| query_duration.max(mutation_duration));
+        Self {
+            query_budget,
+            mutation_budget,
+            idle_eviction_after,
+            buckets: Mutex::new(TokenBuckets::default()),
+        }
+    }
+
+    /// Reads budgets from the environment, falling back to the defaults for unset variables.
+    ///
+    /// Uses `$RATE_LIMIT_QUERY_CAPACITY` (100), `$RATE_LIMIT_QUERY_REFILL_PER_SECOND` (20), `$RATE_LIMIT_MUTATION_CAPACITY` (20) and `$RATE_LIMIT_MUTATION_REFILL_PER_SECOND` (5).
+    /// Returns a description of the first variable which is set but cannot be parsed or is not a positive finite number.
+    pub fn from_env() -> Result<Self, String> {
+        let query_budget = RateLimitBudget {
+            capacity: budget_var_or("RATE_LIMIT_QUERY_CAPACITY", 100.0)?,
+            refill_per_second: budget_var_or("RATE_LIMIT_QUERY_REFILL_PER_SECOND", 20.0)?,
+        };
+        let mutation_budget = RateLimitBudget {
+            capacity: budget_var_or("RATE_LIMIT_MUTATION_CAPACITY", 20.0)?,
+            refill_per_second: budget_var_or("RATE_LIMIT_MUTATION_REFILL_PER_SECOND", 5.0)?,
+        };
+        Ok(Self::new(query_budget, mutation_budget))
+    }
+
+    /// Takes a token from the bucket of a user for a kind of operation.
+    ///
+    /// Returns a GraphQL error with the `RATE_LIMITED` code and a `retryAfter` hint in seconds if the bucket is empty.
+    ///
+    /// * `authorized_user_header` - `Authorized-User` header of the user sending the request.
+    /// * `operation_kind` - Kind of operation of the request.
+    pub fn check(
+        &self,
+        authorized_user_header: &AuthorizedUserHeader,
+        operation_kind: OperationKind,
+    ) -> Result<()> {
+        self.check_at(authorized_user_header, operation_kind, Instant::now())
+    }
+
+    /// Takes a token from the bucket of a user for a kind of operation at an instant.
+    ///
+    /// * `authorized_user_header` - `Authorized-User` header of the user sending the request.
+    /// * `operation_kind` - Kind of operation of the request.
+    /// * `now` - Current instant.
+    fn check_at(
+        &self,
+        authorized_user_header: &AuthorizedUserHeader,
+        operation_kind: OperationKind,
+        now: Instant,
+    ) -> Result<()> {
+        if authorized_user_header.has_permission(Permission::ReadAll)
+            || authorized_user_header.has_permission(Permission::WriteAll)
+        {
+            return Ok(());
+        }
+        let budget = match operation_kind {
+            OperationKind::Query => self.query_budget,
+            OperationKind::Mutation => self.mutation_budget,
+        };
+        // Buckets are consistent after every statement, so a panic while holding the lock leaves them usable.
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let bucket =
+            buckets.use_bucket((authorized_user_header.id(), operation_kind), &budget, now);
+        bucket.refill(&budget, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let retry_after = ((1.0 - bucket.tokens) / budget.refill_per_second)
+            .ceil()
+            .max(1.0) as u64;
+        let message = format!(
+            "Rate limit exceeded for user of UUID: `{}`. Retry after `{}` seconds.",
+            authorized_user_header.id(),
+            retry_after
+        );
+        Err(ShoppingCartError::RateLimited {
+            message,
+            user_id: authorized_user_header.id(),
+            retry_after,
+        }
+        .into())
+    }
+
+    /// Drops the buckets which were not used long enough to be full again.
+    ///
+    /// Only visits the dropped buckets and the least recently used retained bucket, as idle buckets are least recently used.
+    ///
+    /// * `now` - Current instant.
+    fn evict_idle(&self, now: Instant) {
+        let Some(idle_eviction_after) = self.idle_eviction_after else {
+            return;
+        };
+        let mut token_buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let TokenBuckets {
+            buckets, recency, ..
+        } = &mut *token_buckets;
+        while let Some(entry) = recency.first_entry() {
+            let is_idle = buckets.get(entry.get()).is_none_or(|bucket| {
+                now.saturating_duration_since(bucket.refilled_at) >= idle_eviction_after
+            });
+            if !is_idle {
+                break;
+            }
+            buckets.remove(&entry.remove());
+        }
+    }
+}
+
+/// Reads a budget value from an environment variable, falling back to a default if it is not set.
+///
+/// Returns a description of the variable if it cannot be parsed or is not a positive finite number, as zero or non-finite budgets lock users out or break the `retryAfter` hint.
+///
+/// * `key` - Name of the environment variable.
+/// * `default` - Value used if the variable is not set.
+fn budget_var_or(key: &str, default: f64) -> Result<f64, String> {
+    let value = env_var_or(key, default)?;
+    match value.is_finite() && value > 0.0 {
+        true => Ok(value),
+        false => Err(format!(
+            "`${}` is set to `{}`, which is not a positive finite number.",
+            key, value
+        )),
+    }
+}
+
+/// Schema extension taking a token for every operation from the `RateLimiter` in the schema data.
+///
+/// Applies to requests over HTTP and to every operation sent over a WebSocket connection alike.
+/// Operations without `Authorized-User` header are not limited, as they are only permitted to resolve public data.
+pub struct RateLimit;
+
+impl ExtensionFactory for RateLimit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RateLimitExtension)
+    }
+}
+
+/// Extension created by `RateLimit` for each operation.
+struct RateLimitExtension;
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for RateLimitExtension {
+    /// Checks the rate limit once the kind of operation is known from the parsed query.
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        if let (Some(rate_limiter), Some(authorized_user_header)) = (
+            ctx.data_opt::<Arc<RateLimiter>>(),
+            ctx.data_opt::<AuthorizedUserHeader>(),
+        ) {
+            let operation_kind = match document
+                .operations
+                .iter()
+                .any(|(_, operation)| operation.node.ty == OperationType::Mutation)
+            {
+                true => OperationKind::Mutation,
+                false => OperationKind::Query,
+            };
+            rate_limiter
+                .check(authorized_user_header, operation_kind)
+                .map_err(|error| error.into_server_error(Pos::default()))?;
+        }
+        Ok(document)
+    }
+}
+
+/// Periodically drops idle buckets of a rate limiter every `IDLE_EVICTION_INTERVAL`.
+///
+/// Runs until the service stops.
+///
+/// * `rate_limiter` - Rate limiter to drop idle buckets of.
+pub async fn run_rate_limit_eviction(rate_limiter: Arc<RateLimiter>) {
+    let mut interval = tokio::time::interval(IDLE_EVICTION_INTERVAL);
+    loop {
+        interval.tick().await;
+        rate_limiter.evict_idle(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, time::Duration};
+
+    use async_graphql::{
+        http::{WebSocket, WebSocketProtocols},
+        Data, Schema, Value,
+    };
+    use futures::{stream, StreamExt};
+
+    use crate::graphql::{mutation::Mutation, query::Query, subscription::Subscription};
+
+    use super::*;
+
+    /// Budget of one request per second with a burst of two requests.
+    const BUDGET: RateLimitBudget = RateLimitBudget {
+        capacity: 2.0,
+        refill_per_second: 1.0,
+    };
+
+    /// Builds an `Authorized-User` header of a new user.
+    ///
+    /// * `role` - Name of the only role of the user.
+    fn authorized_user_header(role: &str) -> AuthorizedUserHeader {
+        serde_json::from_value(serde_json::json!({"id": Uuid::new(), "roles": [role]})).unwrap()
+    }
+
+    /// Retrieves the `retryAfter` extension of a rate limit error.
+    ///
+    /// * `result` - Result of a rate limit check.
+    fn retry_after(result: Result<()>) -> Option<Value> {
+        result.err()?.extensions?.get("retryAfter").cloned()
+    }
+
+    #[test]
+    fn check_refills_tokens_over_time() {
+        let rate_limiter = RateLimiter::new(BUDGET, BUDGET);
+        let buyer = authorized_user_header("buyer");
+        let start = Instant::now();
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Query, start)
+            .is_ok());
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Query, start)
+            .is_ok());
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Query, start)
+            .is_err());
+        let refilled = start + Duration::from_secs(1);
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Query, refilled)
+            .is_ok());
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Query, refilled)
+            .is_err());
+    }
+
+    #[test]
+    fn check_limits_queries_and_mutations_separately() {
+        let rate_limiter = RateLimiter::new(BUDGET, BUDGET);
+        let buyer = authorized_user_header("buyer");
+        let other_buyer = authorized_user_header("buyer");
+        let now = Instant::now();
+        for _ in 0..2 {
+            assert!(rate_limiter
+                .check_at(&buyer, OperationKind::Query, now)
+                .is_ok());
+        }
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Query, now)
+            .is_err());
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Mutation, now)
+            .is_ok());
+        assert!(rate_limiter
+            .check_at(&other_buyer, OperationKind::Query, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn check_exempts_users_permitted_to_access_all_users() {
+        let rate_limiter = RateLimiter::new(BUDGET, BUDGET);
+        let employee = authorized_user_header("employee");
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(rate_limiter
+                .check_at(&employee, OperationKind::Mutation, now)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn check_hints_seconds_until_next_token() {
+        let rate_limiter = RateLimiter::new(
+            BUDGET,
+            RateLimitBudget {
+                capacity: 1.0,
+                refill_per_second: 0.25,
+            },
+        );
+        let buyer = authorized_user_header("buyer");
+        let now = Instant::now();
+        assert!(rate_limiter
+            .check_at(&buyer, OperationKind::Mutation, now)
+            .is_ok());
+        assert_eq!(
+            retry_after(rate_limiter.check_at(&buyer, OperationKind::Mutation, now)),
+            Some(Value::from(4))
+        );
+        let later = now + Duration::from_secs(3);
+        assert_eq!(
+            retry_after(rate_limiter.check_at(&buyer, OperationKind::Mutation, later)),
+            Some(Value::from(1))
+        );
+    }
+
+    #[test]
+    fn check_drops_least_recently_used_bucket_beyond_maximum() {
+        let rate_limiter = RateLimiter::new(BUDGET, BUDGET);
+        let now = Instant::now();
+        let buyers: Vec<AuthorizedUserHeader> = (0..MAX_RETAINED_BUCKETS)
+            .map(|_| authorized_user_header("buyer"))
+            .collect();
+        for buyer in &buyers {
+            rate_limiter
+                .check_at(buyer, OperationKind::Query, now)
+                .unwrap();
+        }
+        rate_limiter
+            .check_at(&buyers[0], OperationKind::Query, now)
+            .unwrap();
+        rate_limiter
+            .check_at(&authorized_user_header("buyer"), OperationKind::Query, now)
+            .unwrap();
+        let buckets = rate_limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.buckets.len(), MAX_RETAINED_BUCKETS);
+        assert_eq!(buckets.recency.len(), MAX_RETAINED_BUCKETS);
+        assert!(buckets
+            .buckets
+            .contains_key(&(buyers[0].id(), OperationKind::Query)));
+        assert!(!buckets
+            .buckets
+            .contains_key(&(buyers[1].id(), OperationKind::Query)));
+    }
+
+    #[test]
+    fn evict_idle_drops_buckets_full_again() {
+        let rate_limiter = RateLimiter::new(BUDGET, BUDGET);
+        let idle_buyer = authorized_user_header("buyer");
+        let active_buyer = authorized_user_header("buyer");
+        let start = Instant::now();
+        rate_limiter
+            .check_at(&idle_buyer, OperationKind::Query, start)
+            .unwrap();
+        let later = start + Duration::from_secs(1);
+        rate_limiter
+            .check_at(&active_buyer, OperationKind::Query, later)
+            .unwrap();
+        rate_limiter.evict_idle(start + Duration::from_secs(2));
+        let buckets = rate_limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.buckets.len(), 1);
+        assert_eq!(buckets.recency.len(), 1);
+        assert!(buckets
+            .buckets
+            .contains_key(&(active_buyer.id(), OperationKind::Query)));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_throttles_mutations_over_websocket() {
+        let rate_limiter = Arc::new(RateLimiter::new(
+            BUDGET,
+            RateLimitBudget {
+                capacity: 1.0,
+                refill_per_second: 0.001,
+            },
+        ));
+        let schema = Schema::build(Query, Mutation, Subscription)
+            .extension(RateLimit)
+            .data(rate_limiter)
+            .finish();
+        let mut connection_data = Data::default();
+        connection_data.insert(authorized_user_header("buyer"));
+        let mutation =
+            r#"{"query": "mutation { applyMyCoupon(code: \"SUMMER\") { __typename } }"}"#;
+        let client_messages = [
+            r#"{"type": "connection_init"}"#.to_string(),
+            format!(
+                r#"{{"id": "1", "type": "subscribe", "payload": {}}}"#,
+                mutation
+            ),
+            format!(
+                r#"{{"id": "2", "type": "subscribe", "payload": {}}}"#,
+                mutation
+            ),
+        ];
+        let mut websocket = Box::pin(
+            WebSocket::new(
+                schema,
+                stream::iter(client_messages).chain(stream::pending()),
+                WebSocketProtocols::GraphQLWS,
+            )
+            .connection_data(connection_data),
+        );
+        let mut server_messages = Vec::new();
+        while server_messages
+            .iter()
+            .filter(|message: &&String| message.contains(r#""type":"complete""#))
+            .count()
+            < 2
+        {
+            let message = tokio::time::timeout(Duration::from_secs(5), websocket.next())
+                .await
+                .expect("Operations over WebSocket did not complete.")
+                .expect("WebSocket closed before operations completed.");
+            server_messages.push(message.unwrap_text());
+        }
+        let rate_limited_operations = server_messages
+            .iter()
+            .filter(|message| message.contains("RATE_LIMITED"))
+            .count();
+        assert_eq!(rate_limited_operations, 1, "{:?}", server_messages);
+    }
+
+    #[test]
+    fn from_env_rejects_budgets_which_are_not_positive_and_finite() {
+        for value in ["0", "-1", "NaN", "inf"] {
+            env::set_var("RATE_LIMIT_MUTATION_REFILL_PER_SECOND", value);
+            let result = RateLimiter::from_env();
+            env::remove_var("RATE_LIMIT_MUTATION_REFILL_PER_SECOND");
+            let message = result.expect_err(value);
+            assert!(
+                message.contains("$RATE_LIMIT_MUTATION_REFILL_PER_SECOND"),
+                "{}",
+                message
+            );
+        }
+    }
+}